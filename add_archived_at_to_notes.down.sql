@@ -0,0 +1,3 @@
+
+ALTER TABLE notes
+    DROP COLUMN archived_at;
\ No newline at end of file
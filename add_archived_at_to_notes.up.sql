@@ -0,0 +1,3 @@
+
+ALTER TABLE notes
+    ADD COLUMN archived_at TIMESTAMP NULL AFTER batch_id;
\ No newline at end of file
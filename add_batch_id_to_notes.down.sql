@@ -0,0 +1,4 @@
+
+ALTER TABLE notes
+    DROP INDEX idx_notes_batch_id,
+    DROP COLUMN batch_id;
\ No newline at end of file
@@ -0,0 +1,4 @@
+
+ALTER TABLE notes
+    ADD COLUMN batch_id CHAR(36) NULL AFTER reminder_fired_at,
+    ADD INDEX idx_notes_batch_id (batch_id);
\ No newline at end of file
@@ -0,0 +1,6 @@
+
+DELETE FROM notes WHERE deleted_at IS NOT NULL;
+ALTER TABLE notes
+    DROP INDEX idx_notes_live_title,
+    ADD UNIQUE INDEX title (title),
+    DROP COLUMN deleted_at;
\ No newline at end of file
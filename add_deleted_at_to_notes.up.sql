@@ -0,0 +1,5 @@
+
+ALTER TABLE notes
+    ADD COLUMN deleted_at TIMESTAMP NULL AFTER archived_at,
+    DROP INDEX title,
+    ADD UNIQUE INDEX idx_notes_live_title ((IF(deleted_at IS NULL, title, NULL)));
\ No newline at end of file
@@ -0,0 +1,4 @@
+
+ALTER TABLE notes
+    DROP COLUMN featured_order,
+    DROP COLUMN featured;
\ No newline at end of file
@@ -0,0 +1,4 @@
+
+ALTER TABLE notes
+    ADD COLUMN featured BOOLEAN NOT NULL DEFAULT FALSE AFTER visibility,
+    ADD COLUMN featured_order INT NULL AFTER featured;
\ No newline at end of file
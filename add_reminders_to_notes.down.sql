@@ -0,0 +1,4 @@
+
+ALTER TABLE notes
+    DROP COLUMN reminder_fired_at,
+    DROP COLUMN remind_at;
\ No newline at end of file
@@ -0,0 +1,4 @@
+
+ALTER TABLE notes
+    ADD COLUMN remind_at TIMESTAMP NULL AFTER featured_order,
+    ADD COLUMN reminder_fired_at TIMESTAMP NULL AFTER remind_at;
\ No newline at end of file
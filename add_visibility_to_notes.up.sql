@@ -0,0 +1,5 @@
+
+ALTER TABLE notes
+    ADD COLUMN visibility ENUM('private', 'unlisted', 'public') NOT NULL DEFAULT 'private' AFTER is_published;
+
+UPDATE notes SET visibility = IF(is_published, 'public', 'private'), updated_at = updated_at;
\ No newline at end of file
@@ -0,0 +1,66 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{model::NoteEvent, AppState};
+
+const ARCHIVE_BATCH_SIZE: i64 = 100;
+
+pub async fn run(data: Arc<AppState>) {
+    let Some(after_days) = data.config.archive_after_days else {
+        return;
+    };
+    if data.config.archive_interval_secs == 0 {
+        return;
+    }
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(data.config.archive_interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(err) = archive_stale_notes(&data, after_days).await {
+            tracing::error!("error archivando notas: {:?}", err);
+        }
+    }
+}
+
+// Like reminders, each note is claimed with a conditional UPDATE; matching on
+// updated_at as well skips a note that was edited since it was selected.
+async fn archive_stale_notes(data: &AppState, after_days: u64) -> Result<(), sqlx::Error> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(after_days as i64);
+    let mut archived = Vec::new();
+
+    loop {
+        let stale = sqlx::query!(
+            r#"SELECT id, updated_at FROM notes WHERE archived_at IS NULL AND deleted_at IS NULL AND updated_at < ? ORDER BY updated_at, id LIMIT ?"#,
+            cutoff,
+            ARCHIVE_BATCH_SIZE
+        )
+        .fetch_all(&data.db)
+        .await?;
+        let batch_len = stale.len() as i64;
+
+        for note in stale {
+            // Keeps updated_at as is: archiving is not an edit.
+            let claimed = sqlx::query!(
+                r#"UPDATE notes SET archived_at = CURRENT_TIMESTAMP, updated_at = updated_at WHERE id = ? AND updated_at = ? AND archived_at IS NULL AND deleted_at IS NULL"#,
+                &note.id,
+                note.updated_at
+            )
+            .execute(&data.db)
+            .await?;
+
+            if claimed.rows_affected() == 1 {
+                archived.push(note.id);
+            }
+        }
+
+        if batch_len < ARCHIVE_BATCH_SIZE {
+            break;
+        }
+    }
+
+    if data.config.archive_notify && !archived.is_empty() {
+        data.publish(NoteEvent::Archived { ids: archived });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,442 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crate::schema::MAX_CONTENT_BYTES;
+
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub admin_token: Option<String>,
+    pub hide_unpublished: bool,
+    pub report_deleted_notes: bool,
+    pub incomplete_min_content_length: usize,
+    pub incomplete_placeholder_titles: Vec<String>,
+    pub note_lock_ttl_secs: u32,
+    pub list_max_window: Option<usize>,
+    pub trailing_slash: TrailingSlash,
+    pub list_index_hint: Option<String>,
+    pub default_content: Option<String>,
+    pub max_content_bytes: usize,
+    pub note_cap: Option<usize>,
+    pub note_cap_mode: NoteCapMode,
+    pub reject_binary_content: bool,
+    pub cache_policies: HashMap<String, CachePolicy>,
+    pub title_template: Option<String>,
+    pub preview_length: usize,
+    pub read_pool_max_connections: u32,
+    pub write_pool_max_connections: u32,
+    pub database_acquire_timeout_secs: u64,
+    pub database_connect_attempts: u32,
+    pub database_connect_retry_ms: u64,
+    pub read_fallback_to_primary: bool,
+    pub title_case: TitleCase,
+    pub poll_max_timeout_secs: u64,
+    pub poll_max_subscribers: usize,
+    pub reminder_interval_secs: u64,
+    pub archive_after_days: Option<u64>,
+    pub archive_interval_secs: u64,
+    pub archive_notify: bool,
+    pub webhook_urls: Vec<String>,
+    pub webhook_secret: Option<String>,
+    pub webhook_max_attempts: u32,
+    pub field_aliases: HashMap<String, String>,
+    pub truncate_timestamps: bool,
+    pub shutdown_drain_timeout_secs: u64,
+}
+
+// Read endpoints that accept an entry in CACHE_POLICY.
+pub const CACHEABLE_ENDPOINTS: &[&str] = &[
+    "list",
+    "ids",
+    "note",
+    "drafts",
+    "featured",
+    "incomplete",
+    "reminders",
+    "count",
+    "stats",
+    "delta",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CachePolicy {
+    NoStore,
+    ETagOnly,
+    MaxAge(u32),
+}
+
+impl CachePolicy {
+    // `shared` says whether a proxy may keep the response. It may not once an
+    // ADMIN_TOKEN exists, since the body then depends on who asked.
+    pub fn header_value(&self, shared: bool) -> String {
+        match self {
+            CachePolicy::NoStore => "no-store".to_string(),
+            CachePolicy::ETagOnly => "no-cache".to_string(),
+            CachePolicy::MaxAge(secs) if shared => format!("public, max-age={}", secs),
+            CachePolicy::MaxAge(secs) => format!("private, max-age={}", secs),
+        }
+    }
+}
+
+impl FromStr for CachePolicy {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "no-store" => Ok(CachePolicy::NoStore),
+            "etag" => Ok(CachePolicy::ETagOnly),
+            other => other
+                .strip_prefix("max-age=")
+                .and_then(|secs| secs.parse().ok())
+                .map(CachePolicy::MaxAge)
+                .ok_or(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TitleCase {
+    Raw,
+    Title,
+    Sentence,
+}
+
+impl FromStr for TitleCase {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "raw" => Ok(TitleCase::Raw),
+            "title" => Ok(TitleCase::Title),
+            "sentence" => Ok(TitleCase::Sentence),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteCapMode {
+    Evict,
+    Reject,
+}
+
+impl FromStr for NoteCapMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "evict" => Ok(NoteCapMode::Evict),
+            "reject" => Ok(NoteCapMode::Reject),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailingSlash {
+    Strict,
+    Rewrite,
+    Redirect,
+}
+
+impl FromStr for TrailingSlash {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "strict" => Ok(TrailingSlash::Strict),
+            "rewrite" => Ok(TrailingSlash::Rewrite),
+            "redirect" => Ok(TrailingSlash::Redirect),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Config {
+    pub fn init() -> Config {
+        let host = env_or("HOST", "0.0.0.0".to_string());
+        let port = env_or("PORT", 8080);
+        let admin_token = std::env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.trim().is_empty());
+        let hide_unpublished = env_or("HIDE_UNPUBLISHED", false);
+        let report_deleted_notes = env_or("REPORT_DELETED_NOTES", false);
+        let incomplete_min_content_length = env_or("INCOMPLETE_MIN_CONTENT_LENGTH", 1);
+        let incomplete_placeholder_titles = env_list(
+            "INCOMPLETE_PLACEHOLDER_TITLES",
+            &["untitled", "sin titulo", "nueva nota"],
+        );
+        let note_lock_ttl_secs = env_or("NOTE_LOCK_TTL_SECS", 300);
+        let list_max_window = env_opt("LIST_MAX_WINDOW");
+        let trailing_slash = env_or("TRAILING_SLASH", TrailingSlash::Rewrite);
+        // Pasted into the SQL as-is, so only plain index names are accepted.
+        let list_index_hint: Option<String> = env_opt("LIST_INDEX_HINT");
+        if let Some(index) = &list_index_hint {
+            if !index.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                panic!("LIST_INDEX_HINT must be a plain index name, got {:?}", index);
+            }
+        }
+        let max_content_bytes = env_or("MAX_CONTENT_BYTES", MAX_CONTENT_BYTES);
+        let default_content = std::env::var("DEFAULT_CONTENT").ok();
+        if let Some(content) = &default_content {
+            if content.len() > max_content_bytes {
+                panic!("DEFAULT_CONTENT must be at most {} bytes", max_content_bytes);
+            }
+        }
+        let note_cap = env_opt("NOTE_CAP");
+        if note_cap == Some(0) {
+            panic!("NOTE_CAP must be greater than zero");
+        }
+        let note_cap_mode = env_or("NOTE_CAP_MODE", NoteCapMode::Evict);
+        let reject_binary_content = env_or("REJECT_BINARY_CONTENT", false);
+        let cache_policies = env_cache_policies("CACHE_POLICY");
+        let title_template = env_opt("TITLE_TEMPLATE");
+        let preview_length = env_or("PREVIEW_LENGTH", 200);
+        // DATABASE_MAX_CONNECTIONS sizes both pools unless one is set on its own.
+        let database_max_connections = env_nonzero("DATABASE_MAX_CONNECTIONS", 10);
+        let read_pool_max_connections =
+            env_nonzero("READ_POOL_MAX_CONNECTIONS", database_max_connections);
+        let write_pool_max_connections =
+            env_nonzero("WRITE_POOL_MAX_CONNECTIONS", database_max_connections);
+        let database_acquire_timeout_secs = env_nonzero("DATABASE_ACQUIRE_TIMEOUT_SECS", 30);
+        let database_connect_attempts = env_nonzero("DATABASE_CONNECT_ATTEMPTS", 10);
+        let database_connect_retry_ms = env_or("DATABASE_CONNECT_RETRY_MS", 500);
+        let read_fallback_to_primary = env_or("READ_FALLBACK_TO_PRIMARY", true);
+        let title_case = env_or("TITLE_CASE", TitleCase::Raw);
+        let poll_max_timeout_secs = env_or("POLL_MAX_TIMEOUT_SECS", 60);
+        let poll_max_subscribers = env_or("POLL_MAX_SUBSCRIBERS", 100);
+        let reminder_interval_secs = env_or("REMINDER_INTERVAL_SECS", 30);
+        let archive_after_days = env_opt("ARCHIVE_AFTER_DAYS");
+        let archive_interval_secs = env_or("ARCHIVE_INTERVAL_SECS", 3600);
+        let archive_notify = env_or("ARCHIVE_NOTIFY", false);
+        // Not env_list: URLs must keep their case.
+        let webhook_urls = std::env::var("WEBHOOK_URLS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|url| url.trim().to_string())
+                    .filter(|url| !url.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let webhook_secret = env_opt("WEBHOOK_SECRET");
+        let webhook_max_attempts = env_or("WEBHOOK_MAX_ATTEMPTS", 3);
+        let field_aliases = env_field_aliases("FIELD_ALIASES");
+        let truncate_timestamps = env_or("TRUNCATE_TIMESTAMPS", false);
+        let shutdown_drain_timeout_secs = env_or("SHUTDOWN_DRAIN_TIMEOUT_SECS", 30);
+
+        Config {
+            host,
+            port,
+            admin_token,
+            hide_unpublished,
+            report_deleted_notes,
+            incomplete_min_content_length,
+            incomplete_placeholder_titles,
+            note_lock_ttl_secs,
+            list_max_window,
+            trailing_slash,
+            list_index_hint,
+            default_content,
+            max_content_bytes,
+            note_cap,
+            note_cap_mode,
+            reject_binary_content,
+            cache_policies,
+            title_template,
+            preview_length,
+            read_pool_max_connections,
+            write_pool_max_connections,
+            database_acquire_timeout_secs,
+            database_connect_attempts,
+            database_connect_retry_ms,
+            read_fallback_to_primary,
+            title_case,
+            poll_max_timeout_secs,
+            poll_max_subscribers,
+            reminder_interval_secs,
+            archive_after_days,
+            archive_interval_secs,
+            archive_notify,
+            webhook_urls,
+            webhook_secret,
+            webhook_max_attempts,
+            field_aliases,
+            truncate_timestamps,
+            shutdown_drain_timeout_secs,
+        }
+    }
+}
+
+// What init gives with nothing set, built without reading the environment so
+// tests do not depend on the shell they run in.
+#[cfg(test)]
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            admin_token: None,
+            hide_unpublished: false,
+            report_deleted_notes: false,
+            incomplete_min_content_length: 1,
+            incomplete_placeholder_titles: vec![
+                "untitled".to_string(),
+                "sin titulo".to_string(),
+                "nueva nota".to_string(),
+            ],
+            note_lock_ttl_secs: 300,
+            list_max_window: None,
+            trailing_slash: TrailingSlash::Rewrite,
+            list_index_hint: None,
+            default_content: None,
+            max_content_bytes: MAX_CONTENT_BYTES,
+            note_cap: None,
+            note_cap_mode: NoteCapMode::Evict,
+            reject_binary_content: false,
+            cache_policies: HashMap::new(),
+            title_template: None,
+            preview_length: 200,
+            read_pool_max_connections: 10,
+            write_pool_max_connections: 10,
+            database_acquire_timeout_secs: 30,
+            database_connect_attempts: 10,
+            database_connect_retry_ms: 500,
+            read_fallback_to_primary: true,
+            title_case: TitleCase::Raw,
+            poll_max_timeout_secs: 60,
+            poll_max_subscribers: 100,
+            reminder_interval_secs: 30,
+            archive_after_days: None,
+            archive_interval_secs: 3600,
+            archive_notify: false,
+            webhook_urls: Vec::new(),
+            webhook_secret: None,
+            webhook_max_attempts: 3,
+            field_aliases: HashMap::new(),
+            truncate_timestamps: false,
+            shutdown_drain_timeout_secs: 30,
+        }
+    }
+}
+
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    match std::env::var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a valid value, got {:?}", key, value)),
+        Err(_) => default,
+    }
+}
+
+fn env_nonzero<T: FromStr + PartialEq + From<u8>>(key: &str, default: T) -> T {
+    let value = env_or(key, default);
+    if value == T::from(0) {
+        panic!("{} must be greater than zero", key);
+    }
+    value
+}
+
+fn env_opt<T: FromStr>(key: &str) -> Option<T> {
+    std::env::var(key)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("{} must be a valid value, got {:?}", key, value))
+        })
+}
+
+fn env_list(key: &str, default: &[&str]) -> Vec<String> {
+    match std::env::var(key) {
+        Ok(value) => value
+            .split(',')
+            .map(|item| item.trim().to_lowercase())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        Err(_) => default.iter().map(|item| item.to_string()).collect(),
+    }
+}
+
+fn env_cache_policies(key: &str) -> HashMap<String, CachePolicy> {
+    match std::env::var(key) {
+        Ok(value) => parse_cache_policies(key, &value),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn parse_cache_policies(key: &str, value: &str) -> HashMap<String, CachePolicy> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let parsed = entry.split_once(':').and_then(|(endpoint, policy)| {
+                let endpoint = endpoint.trim().to_lowercase();
+                let policy = policy.parse::<CachePolicy>().ok()?;
+                CACHEABLE_ENDPOINTS
+                    .contains(&endpoint.as_str())
+                    .then_some((endpoint, policy))
+            });
+            parsed.unwrap_or_else(|| panic!("{} must be a valid value, got {:?}", key, entry))
+        })
+        .collect()
+}
+
+// FIELD_ALIASES="name:title,text:content" maps each legacy body field to the
+// field it stands for.
+fn env_field_aliases(key: &str) -> HashMap<String, String> {
+    let Ok(value) = std::env::var(key) else {
+        return HashMap::new();
+    };
+
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let parsed = entry.split_once(':').and_then(|(alias, field)| {
+                let (alias, field) = (alias.trim(), field.trim());
+                (!alias.is_empty() && !field.is_empty() && alias != field)
+                    .then(|| (alias.to_string(), field.to_string()))
+            });
+            parsed.unwrap_or_else(|| panic!("{} must be a valid value, got {:?}", key, entry))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_policy_parses_each_form() {
+        assert_eq!("no-store".parse(), Ok(CachePolicy::NoStore));
+        assert_eq!(" ETag ".parse(), Ok(CachePolicy::ETagOnly));
+        assert_eq!("max-age=60".parse(), Ok(CachePolicy::MaxAge(60)));
+        assert_eq!("max-age=".parse::<CachePolicy>(), Err(()));
+        assert_eq!("forever".parse::<CachePolicy>(), Err(()));
+    }
+
+    #[test]
+    fn max_age_is_private_unless_shared() {
+        let policy = CachePolicy::MaxAge(60);
+        assert_eq!(policy.header_value(true), "public, max-age=60");
+        assert_eq!(policy.header_value(false), "private, max-age=60");
+        assert_eq!(CachePolicy::NoStore.header_value(true), "no-store");
+    }
+
+    #[test]
+    fn cache_policies_map_endpoints() {
+        let policies = parse_cache_policies("CACHE_POLICY", "list:etag, Note:max-age=60,");
+        assert_eq!(policies.len(), 2);
+        assert_eq!(policies["list"], CachePolicy::ETagOnly);
+        assert_eq!(policies["note"], CachePolicy::MaxAge(60));
+    }
+
+    #[test]
+    #[should_panic(expected = "CACHE_POLICY must be a valid value")]
+    fn cache_policies_reject_unknown_endpoints() {
+        parse_cache_policies("CACHE_POLICY", "notes:etag");
+    }
+}
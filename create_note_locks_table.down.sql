@@ -0,0 +1,2 @@
+
+DROP TABLE IF EXISTS note_locks;
\ No newline at end of file
@@ -0,0 +1,7 @@
+
+CREATE TABLE IF NOT EXISTS note_locks (
+    note_id CHAR(36) PRIMARY KEY NOT NULL,
+    holder VARCHAR(255) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (note_id) REFERENCES notes(id) ON DELETE CASCADE
+);
\ No newline at end of file
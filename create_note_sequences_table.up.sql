@@ -0,0 +1,5 @@
+
+CREATE TABLE IF NOT EXISTS note_sequences (
+    name VARCHAR(64) PRIMARY KEY NOT NULL,
+    value BIGINT UNSIGNED NOT NULL
+);
\ No newline at end of file
@@ -0,0 +1,85 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::{
+    model::{NoteLockModel, NoteModelResponse},
+    schema::FieldError,
+};
+
+// Every handler error goes through here, so clients always get
+// `{"status": "error", "message": ...}` plus the extra key a variant carries.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    // The note changed since the client last read it; carries the current one.
+    Stale(Box<NoteModelResponse>),
+    Locked(NoteLockModel),
+    // Soft-deleted, reported apart from a plain 404 with REPORT_DELETED_NOTES.
+    NoteDeleted(String),
+    Unprocessable(String),
+    Validation(Vec<FieldError>),
+    Unavailable(String),
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        AppError::Database(err)
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, message, extra) = match self {
+            AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message, None),
+            AppError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message, None),
+            AppError::Forbidden(message) => (StatusCode::FORBIDDEN, message, None),
+            AppError::NotFound(message) => (StatusCode::NOT_FOUND, message, None),
+            AppError::Conflict(message) => (StatusCode::CONFLICT, message, None),
+            AppError::Stale(note) => (
+                StatusCode::CONFLICT,
+                "La nota fue modificada por otro cliente".to_string(),
+                Some(("data", serde_json::json!({ "note": note }))),
+            ),
+            AppError::Locked(lock) => (
+                StatusCode::LOCKED,
+                "La nota esta bloqueada por otro editor".to_string(),
+                Some(("data", serde_json::json!({ "lock": lock }))),
+            ),
+            AppError::NoteDeleted(message) => (
+                StatusCode::GONE,
+                message,
+                Some(("code", serde_json::json!("NOTE_DELETED"))),
+            ),
+            AppError::Unprocessable(message) => (StatusCode::UNPROCESSABLE_ENTITY, message, None),
+            AppError::Validation(errors) => (
+                StatusCode::BAD_REQUEST,
+                "La nota no es valida".to_string(),
+                Some(("errors", serde_json::json!(errors))),
+            ),
+            AppError::Unavailable(message) => (StatusCode::SERVICE_UNAVAILABLE, message, None),
+            AppError::Database(err) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", err),
+                None,
+            ),
+        };
+
+        let mut error_response = serde_json::json!({
+            "status": "error",
+            "message": message,
+        });
+        if let Some((key, value)) = extra {
+            error_response[key] = value;
+        }
+
+        (status, Json(error_response)).into_response()
+    }
+}
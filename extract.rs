@@ -0,0 +1,31 @@
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    Json,
+};
+
+use crate::error::AppError;
+
+// Drop-in for `Json` in handler arguments. A body axum cannot parse, or one
+// that does not fit `T`, becomes a 400 in the usual error envelope instead
+// of axum's plain-text rejection.
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(ApiJson(value)),
+            Err(rejection) => Err(AppError::BadRequest(format!(
+                "Cuerpo JSON invalido: {}",
+                rejection.body_text()
+            ))),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+
+use axum::{extract::Request, http::HeaderMap, middleware::Next, response::Response};
+
+pub const FEATURES_HEADER: &str = "x-features";
+
+// Experimental behaviors a client can opt into per request. Anything else in
+// the header is ignored.
+pub const FUZZY_SEARCH: &str = "fuzzy-search";
+const KNOWN_FEATURES: &[&str] = &[FUZZY_SEARCH];
+
+#[derive(Clone, Debug, Default)]
+pub struct Features(HashSet<&'static str>);
+
+impl Features {
+    pub fn enabled(&self, feature: &str) -> bool {
+        self.0.contains(feature)
+    }
+}
+
+pub async fn parse_features(mut req: Request, next: Next) -> Response {
+    let features = features_from(req.headers());
+    req.extensions_mut().insert(features);
+    next.run(req).await
+}
+
+fn features_from(headers: &HeaderMap) -> Features {
+    let features = headers
+        .get_all(FEATURES_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| {
+            let name = name.trim().to_lowercase();
+            KNOWN_FEATURES.iter().find(|known| **known == name).copied()
+        })
+        .collect();
+    Features(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_are_read_from_every_header_value() {
+        let mut headers = HeaderMap::new();
+        headers.append(FEATURES_HEADER, "Fuzzy-Search, unknown".parse().unwrap());
+        headers.append(FEATURES_HEADER, " other ".parse().unwrap());
+        let features = features_from(&headers);
+        assert!(features.enabled(FUZZY_SEARCH));
+        assert!(!features.enabled("unknown"));
+        assert!(!features.enabled("other"));
+    }
+
+    #[test]
+    fn no_header_enables_nothing() {
+        assert!(!features_from(&HeaderMap::new()).enabled(FUZZY_SEARCH));
+    }
+}
@@ -1,294 +1,3493 @@
-use std::sync::Arc;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 
 use axum::{
+    body::Body,
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    Json,
+    http::{
+        header::{
+            AUTHORIZATION, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, ETAG,
+            IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LINK, LOCATION, VARY,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
-use serde_json::json;
+use chrono::SubsecRound;
+use futures_util::StreamExt;
+use sqlx::{mysql::MySqlDatabaseError, MySql, Row};
+use tokio::sync::broadcast;
 
 use crate::{
-    model::{NoteModel, NoteModelResponse},
-    schema::{CreateNoteSchema, FilterOptions, UpdateNoteSchema},
+    config::{Config, NoteCapMode, TitleCase},
+    error::AppError,
+    extract::ApiJson,
+    feature::{Features, FUZZY_SEARCH},
+    model::{NoteEvent, NoteListRow, NoteLockModel, NoteModel, NoteModelResponse},
+    preview::markdown_preview,
+    schema::{
+        looks_binary, validate_content, AutosaveNoteSchema, BulkDeleteSchema, BulkSetSchema,
+        CountOptions, CreateNoteSchema, DeltaOptions, EditNoteOptions, FeatureNoteSchema,
+        FieldError, FilterOptions, IdsOptions, ImportOptions, MergeNotesSchema, NoteOptions,
+        PollOptions, StatsOptions, UpdateNoteSchema,
+    },
     AppState,
 };
 
-pub async fn health_check_handler() -> impl IntoResponse {
+// Runs a read on the replica and, when READ_FALLBACK_TO_PRIMARY is on and the
+// replica itself failed, runs it once more on the primary. The read is pasted
+// in twice, so only pass idempotent SELECTs here; a QueryBuilder query has to
+// be built inside the block because a built one cannot be reused.
+macro_rules! read_with_fallback {
+    ($data:expr, |$pool:ident| $read:expr) => {{
+        let $pool = &$data.read_db;
+        match $read {
+            Err(err) if $data.config.read_fallback_to_primary && is_replica_failure(&err) => {
+                tracing::warn!(
+                    "lectura fallida en la replica, reintentando en la primaria: {:?}",
+                    err
+                );
+                let $pool = &$data.db;
+                $read
+            }
+            result => result,
+        }
+    }};
+}
+
+pub async fn health_check_handler(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
     const MESSAGE: &str = "API";
 
+    // Load balancers poll this, so a down database must not answer 200.
+    sqlx::query("SELECT 1")
+        .execute(&data.db)
+        .await
+        .map_err(|e| AppError::Unavailable(format!("Base de datos no disponible: {}", e)))?;
+
     let json_response = serde_json::json!({
         "status": "ok",
         "message": MESSAGE
     });
 
-    Json(json_response)
+    Ok(Json(json_response))
 }
 
-pub async fn note_list_handler(
-    opts: Option<Query<FilterOptions>>,
+pub async fn schema_info_handler(
+    headers: HeaderMap,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    
-    let Query(opts) = opts.unwrap_or_default();
-
-    let limit = opts.limit.unwrap_or(10);
-    let offset = (opts.page.unwrap_or(1) - 1) * limit;
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&headers, &data.config)?;
 
-   
-    let notes = sqlx::query_as!(
-        NoteModel,
-        r#"SELECT * FROM notes ORDER by id LIMIT ? OFFSET ?"#,
-        limit as i32,
-        offset as i32
-    )
-    .fetch_all(&data.db)
-    .await
-    .map_err(|e| {
-        let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("Database error: { }", e),
-        });
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    let rows = read_with_fallback!(data, |pool| {
+        sqlx::query(
+            r#"SELECT COLUMN_NAME, COLUMN_TYPE, IS_NULLABLE, COLUMN_KEY FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'notes' ORDER BY ORDINAL_POSITION"#,
+        )
+        .fetch_all(pool)
+        .await
     })?;
 
-    
-    let note_responses = notes
+    let columns = rows
         .iter()
-        .map(|note| to_note_response(&note))
-        .collect::<Vec<NoteModelResponse>>();
+        .map(|row| {
+            let nullable: String = row.get("IS_NULLABLE");
+            serde_json::json!({
+                "name": row.get::<String, _>("COLUMN_NAME"),
+                "type": row.get::<String, _>("COLUMN_TYPE"),
+                "nullable": nullable == "YES",
+                "key": row.get::<String, _>("COLUMN_KEY"),
+            })
+        })
+        .collect::<Vec<serde_json::Value>>();
 
     let json_response = serde_json::json!({
-        "status": "ok",
-        "count": note_responses.len(),
-        "notes": note_responses
+        "status": "success",
+        "data": serde_json::json!({
+            "table": "notes",
+            "columns": columns
+        })
     });
 
     Ok(Json(json_response))
 }
 
-pub async fn create_note_handler(
+// Dry run of the list filter for query-builder UIs. Reports the WHERE and
+// ORDER BY the list endpoint would use, with placeholders standing in for
+// the bound values, how many notes match and MySQL's plan for the query.
+// Only search, is_published, sort_by and order are read from the body.
+pub async fn explain_note_filter_handler(
+    headers: HeaderMap,
+    Extension(features): Extension<Features>,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<CreateNoteSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    
-    let id = uuid::Uuid::new_v4().to_string();
-    let query_result = sqlx::query(r#"INSERT INTO notes (id, title, content) VALUES (?, ?, ?)"#)
-        .bind(&id)
-        .bind(&body.title)
-        .bind(&body.content)
-        .execute(&data.db)
-        .await
-        .map_err(|err: sqlx::Error| err.to_string());
+    ApiJson(filter): ApiJson<FilterOptions>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&headers, &data.config)?;
+    let access = Access::of(&data.config, &headers);
 
-    
-    if let Err(err) = query_result {
-        if err.contains("Duplicate entry") {
-            let error_response = serde_json::json!({
-                "status": "error",
-                "message": "Note already exists",
-            });
-            return Err((StatusCode::CONFLICT, Json(error_response)));
-        }
+    let (sort_by, order) = list_order(&filter)?;
+    let search = filter
+        .search
+        .as_deref()
+        .filter(|search| !search.trim().is_empty());
 
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"status": "error","message": format!("{:?}", err)})),
-        ));
+    let mut where_clause = sqlx::QueryBuilder::<MySql>::new("");
+    push_list_filter(
+        &mut where_clause,
+        search,
+        filter.is_published,
+        access,
+        &features,
+    );
+    let where_clause = where_clause.sql().trim().to_string();
+    let mut order_by = format!("ORDER BY {} {}", sort_by, order);
+    if sort_by != "id" {
+        order_by.push_str(&format!(", id {}", order));
     }
 
-    
-    let note = sqlx::query_as!(NoteModel, r#"SELECT * FROM notes WHERE id = ?"#, &id)
-        .fetch_one(&data.db)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", e)})),
-            )
-        })?;
+    let count = read_with_fallback!(data, |pool| {
+        let mut query = sqlx::QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM notes");
+        push_list_filter(&mut query, search, filter.is_published, access, &features);
+        query.build_query_scalar::<i64>().fetch_one(pool).await
+    })?;
 
-    let note_response = serde_json::json!({
-            "status": "success",
-            "data": serde_json::json!({
-                "note": to_note_response(&note)
+    let plan = read_with_fallback!(data, |pool| {
+        let select = select_notes(&data.config, false);
+        let mut query = sqlx::QueryBuilder::<MySql>::new(format!("EXPLAIN {}", select.sql()));
+        push_list_filter(&mut query, search, filter.is_published, access, &features);
+        query.push(format!(" {}", order_by));
+        query.build().fetch_all(pool).await
+    })?;
+
+    // `rows` is BIGINT UNSIGNED on some servers and signed on others.
+    let plan = plan
+        .iter()
+        .map(|row| {
+            let text = |column: &str| row.try_get::<Option<String>, _>(column).ok().flatten();
+            serde_json::json!({
+                "table": text("table"),
+                "access_type": text("type"),
+                "possible_keys": text("possible_keys"),
+                "key": text("key"),
+                "rows": row.try_get_unchecked::<Option<i64>, _>("rows").ok().flatten(),
+                "extra": text("Extra"),
+            })
+        })
+        .collect::<Vec<serde_json::Value>>();
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "where": where_clause,
+            "order_by": order_by,
+            "count": count,
+            "plan": plan
         })
     });
 
-    Ok(Json(note_response))
+    Ok(Json(json_response))
 }
 
-pub async fn get_note_handler(
-    Path(id): Path<String>,
+pub async fn note_list_handler(
+    opts: Option<Query<FilterOptions>>,
+    headers: HeaderMap,
+    Extension(features): Extension<Features>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    
-    let query_result = sqlx::query_as!(
-        NoteModel,
-        r#"SELECT * FROM notes WHERE id = ?"#,
-        &id
-    )
-    .fetch_one(&data.db)
-    .await;
-
+) -> Result<Response, AppError> {
     
-    match query_result {
-        Ok(note) => {
-            let note_response = serde_json::json!({
-                "status": "success",
-                "data": serde_json::json!({
-                    "note": to_note_response(&note)
-                })
-            });
+    let Query(opts) = opts.unwrap_or_default();
+    let pagination = pagination(&opts)?;
+    let (sort_by, order) = list_order(&opts)?;
+    let access = Access::of(&data.config, &headers);
 
-            return Ok(Json(note_response));
+    // Cursor mode walks ids upwards, so it takes neither a page number nor
+    // another sort.
+    if opts.after.is_some() {
+        if opts.page.is_some() {
+            return Err(AppError::BadRequest(
+                "after y page son excluyentes; use solo uno".to_string(),
+            ));
         }
-        Err(sqlx::Error::RowNotFound) => {
-            let error_response = serde_json::json!({
-                "status": "fail",
-                "message": format!("La nota con el ID: {} no encontrado", id)
-            });
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
+        if sort_by != "id" || order != "ASC" {
+            return Err(AppError::BadRequest(
+                "after solo admite sort_by=id y order=asc".to_string(),
+            ));
         }
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", e)})),
+    }
+
+    if let Some(max_window) = data.config.list_max_window {
+        if pagination.offset + pagination.limit > max_window {
+            return Err(AppError::BadRequest(format!(
+                    "No se pueden consultar mas de {} notas en total (offset + limit); use filtros para acotar la busqueda",
+                    max_window
+                )));
+        }
+    }
+
+    let search = opts
+        .search
+        .as_deref()
+        .filter(|search| !search.trim().is_empty());
+
+    let (total, last_updated, last_archived, last_fired, last_deleted) =
+        read_with_fallback!(data, |pool| {
+            list_summary_query(search, opts.is_published, access, &features)
+                .build_query_as::<(
+                    i64,
+                    Option<chrono::DateTime<chrono::Utc>>,
+                    Option<chrono::DateTime<chrono::Utc>>,
+                    Option<chrono::DateTime<chrono::Utc>>,
+                    Option<chrono::DateTime<chrono::Utc>>,
+                )>()
+                .fetch_one(pool)
+                .await
+        })?;
+    let last_modified = [last_updated, last_archived, last_fired, last_deleted]
+        .into_iter()
+        .flatten()
+        .max();
+
+    // If-None-Match wins over If-Modified-Since when a client sends both.
+    if let (Some(last_modified), Some(since)) = (last_modified, if_modified_since(&headers)) {
+        if last_modified <= since && !headers.contains_key(IF_NONE_MATCH) {
+            return Ok(with_cache_policy(
+                &data.config,
+                "list",
+                (
+                    StatusCode::NOT_MODIFIED,
+                    [(LAST_MODIFIED, http_date(last_modified))],
+                ),
             ));
         }
-    };
-}
+    }
 
-pub async fn edit_note_handler(
-    Path(id): Path<String>,
-    State(data): State<Arc<AppState>>,
-    Json(body): Json<UpdateNoteSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
    
-    let query_result = sqlx::query_as!(
-        NoteModel,
-        r#"SELECT * FROM notes WHERE id = ?"#,
-        &id
-    )
-    .fetch_one(&data.db)
-    .await;
-
-    
-    let note = match query_result {
-        Ok(note) => note,
-        Err(sqlx::Error::RowNotFound) => {
-            let error_response = serde_json::json!({
-                "status": "error",
-                "message": format!("La nota con el ID: {} no encontrado", id)
-            });
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    let with_content_length = opts.with_content_length.unwrap_or(false);
+    let mut rows = read_with_fallback!(data, |pool| {
+        let mut query = select_notes(&data.config, with_content_length);
+        push_list_filter(&mut query, search, opts.is_published, access, &features);
+        if let Some(after) = &opts.after {
+            query.push(" AND id > ");
+            query.push_bind(after.clone());
         }
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "status": "error",
-                    "message": format!("{:?}", e)
-                })),
-            ));
+        query.push(format!(" ORDER BY {} {}", sort_by, order));
+        // id breaks ties so rows sharing a created_at or title keep their
+        // place and never show up on two pages or none.
+        if sort_by != "id" {
+            query.push(format!(", id {}", order));
         }
+        // One extra row in cursor mode tells whether another page follows.
+        let limit = pagination.limit + opts.after.is_some() as usize;
+        query.push(" LIMIT ");
+        query.push_bind(limit as i64);
+        query.push(" OFFSET ");
+        query.push_bind(pagination.offset as i64);
+        query.build_query_as::<NoteListRow>().fetch_all(pool).await
+    })?;
+
+    let next_cursor = if opts.after.is_some() && rows.len() > pagination.limit {
+        rows.truncate(pagination.limit);
+        rows.last().map(|row| row.note.id.clone())
+    } else {
+        None
     };
 
-   
-    let is_published = body.is_published.unwrap_or(note.is_published != 0);
-    let i8_is_published = is_published as i8;
+    let (notes, content_lengths): (Vec<NoteModel>, Vec<Option<i64>>) = rows
+        .into_iter()
+        .map(|row| (row.note, row.content_length))
+        .unzip();
 
     
-    let update_result =
-        sqlx::query(r#"UPDATE notes SET title = ?, content = ?, is_published = ? WHERE id = ?"#)
-            .bind(&body.title.unwrap_or_else(|| note.title))
-            .bind(&body.content.unwrap_or_else(|| note.content))
-            .bind(i8_is_published)
-            .bind(&id)
-            .execute(&data.db)
+    let include_age = opts.include_age.unwrap_or(false);
+    let with_preview = opts.with_preview.unwrap_or(false);
+    let applied = serde_json::json!({
+        "page": pagination.page,
+        "limit": pagination.limit,
+        "include_age": include_age,
+        "search": search,
+        "is_published": opts.is_published,
+        "sort_by": sort_by,
+        "order": order.to_lowercase(),
+        "with_content_length": with_content_length,
+        "with_preview": with_preview,
+        "after": opts.after
+    });
+    let etag = list_etag(&notes, last_modified, total, &applied);
+    if if_none_match(&headers, &etag) {
+        let mut response = with_cache_policy(
+            &data.config,
+            "list",
+            (StatusCode::NOT_MODIFIED, [(ETAG, etag)]),
+        );
+        if let Some(last_modified) = last_modified {
+            if let Ok(value) = HeaderValue::from_str(&http_date(last_modified)) {
+                response.headers_mut().insert(LAST_MODIFIED, value);
+            }
+        }
+        return Ok(response);
+    }
+
+    let note_responses = notes
+        .iter()
+        .zip(content_lengths)
+        .map(|(note, content_length)| NoteModelResponse {
+            content_length,
+            preview: with_preview
+                .then(|| markdown_preview(&note.content, data.config.preview_length)),
+            ..with_age(to_note_response(&data.config, note), include_age)
+        })
+        .collect::<Vec<NoteModelResponse>>();
+
+    let mut response = if opts.after.is_some() {
+        cursor_list_response(
+            &data.config,
+            pagination.limit,
+            total,
+            note_responses,
+            applied,
+            next_cursor,
+        )
+    } else {
+        paginated_response(
+            &data.config,
+            "list",
+            "/api/notes",
+            &pagination,
+            total,
+            note_responses,
+            applied,
+        )
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(ETAG, value);
+    }
+    if let Some(last_modified) = last_modified {
+        if let Ok(value) = HeaderValue::from_str(&http_date(last_modified)) {
+            response.headers_mut().insert(LAST_MODIFIED, value);
+        }
+    }
+
+    Ok(response)
+}
+
+// Same notes as the main list, without bodies or paging, so sync clients can
+// diff ids before fetching anything.
+pub async fn note_ids_handler(
+    opts: Option<Query<IdsOptions>>,
+    headers: HeaderMap,
+    Extension(features): Extension<Features>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let access = Access::of(&data.config, &headers);
+    let with_updated_at = opts.with_updated_at.unwrap_or(false);
+
+    let search = opts
+        .search
+        .as_deref()
+        .filter(|search| !search.trim().is_empty());
+
+    let rows = read_with_fallback!(data, |pool| {
+        let mut query = sqlx::QueryBuilder::<MySql>::new("SELECT id, updated_at FROM notes");
+        push_list_filter(&mut query, search, opts.is_published, access, &features);
+        query.push(" ORDER BY id");
+        query
+            .build_query_as::<(String, Option<chrono::DateTime<chrono::Utc>>)>()
+            .fetch_all(pool)
             .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "status": "error",
-                        "message": format!("{:?}", e)
-                    })),
-                )
-            })?;
+    })?;
 
-    
-    if update_result.rows_affected() == 0 {
-        let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("La nota con el ID: {} no encontrado", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    let ids = rows
+        .into_iter()
+        .map(|(id, updated_at)| {
+            if with_updated_at {
+                serde_json::json!({ "id": id, "updated_at": updated_at })
+            } else {
+                serde_json::json!(id)
+            }
+        })
+        .collect::<Vec<serde_json::Value>>();
+
+    let json_response = serde_json::json!({
+        "status": "ok",
+        "count": ids.len(),
+        "ids": ids
+    });
+
+    Ok(with_cache_policy(&data.config, "ids", Json(json_response)))
+}
+
+pub async fn draft_note_list_handler(
+    opts: Option<Query<FilterOptions>>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let pagination = pagination(&opts)?;
+    let access = Access::of(&data.config, &headers);
+
+    let notes = read_with_fallback!(data, |pool| {
+        sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE is_published = 0 AND archived_at IS NULL AND deleted_at IS NULL AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?) ORDER BY updated_at DESC, id LIMIT ? OFFSET ?"#,
+            access.private,
+            access.unpublished,
+            pagination.limit as i64,
+            pagination.offset as i64
+        )
+        .fetch_all(pool)
+        .await
+    })?;
+
+    let total = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS count FROM notes WHERE is_published = 0 AND archived_at IS NULL AND deleted_at IS NULL AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?)"#,
+            access.private,
+            access.unpublished
+        )
+        .fetch_one(pool)
+        .await
+    })?;
+    let include_age = opts.include_age.unwrap_or(false);
+    let note_responses = notes
+        .iter()
+        .map(|note| with_age(to_note_response(&data.config, note), include_age))
+        .collect::<Vec<NoteModelResponse>>();
+
+    Ok(paginated_response(
+        &data.config,
+        "drafts",
+        "/api/notes/drafts",
+        &pagination,
+        total,
+        note_responses,
+        serde_json::json!({
+            "page": pagination.page,
+            "limit": pagination.limit,
+            "include_age": include_age
+        }),
+    ))
+}
+
+pub async fn featured_note_list_handler(
+    opts: Option<Query<FilterOptions>>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let pagination = pagination(&opts)?;
+    let access = Access::of(&data.config, &headers);
+
+    let notes = read_with_fallback!(data, |pool| {
+        sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE featured = 1 AND is_published = 1 AND visibility <> 'unlisted' AND deleted_at IS NULL AND (visibility <> 'private' OR ?) ORDER BY featured_order IS NULL, featured_order, id LIMIT ? OFFSET ?"#,
+            access.private,
+            pagination.limit as i64,
+            pagination.offset as i64
+        )
+        .fetch_all(pool)
+        .await
+    })?;
+
+    let total = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS count FROM notes WHERE featured = 1 AND is_published = 1 AND visibility <> 'unlisted' AND deleted_at IS NULL AND (visibility <> 'private' OR ?)"#,
+            access.private
+        )
+        .fetch_one(pool)
+        .await
+    })?;
+    let include_age = opts.include_age.unwrap_or(false);
+    let note_responses = notes
+        .iter()
+        .map(|note| with_age(to_note_response(&data.config, note), include_age))
+        .collect::<Vec<NoteModelResponse>>();
+
+    Ok(paginated_response(
+        &data.config,
+        "featured",
+        "/api/notes/featured",
+        &pagination,
+        total,
+        note_responses,
+        serde_json::json!({
+            "page": pagination.page,
+            "limit": pagination.limit,
+            "include_age": include_age
+        }),
+    ))
+}
+
+// Every note stamped with the batch id an import returned, unlisted ones
+// included, so importers can check what was actually stored. Private notes
+// still need the admin token once one is set.
+pub async fn note_batch_handler(
+    Path(batch_id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    if uuid::Uuid::parse_str(&batch_id).is_err() {
+        return Err(AppError::BadRequest(format!(
+            "El lote: {} no es un UUID valido",
+            batch_id
+        )));
     }
+    let access = Access::of(&data.config, &headers);
 
-    
-    let updated_note = sqlx::query_as!(
-        NoteModel,
-        r#"SELECT * FROM notes WHERE id = ?"#,
-        &id
-    )
-    .fetch_one(&data.db)
-    .await
-    .map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"status": "error","message": format!("{:?}", e)})),
+    let notes = read_with_fallback!(data, |pool| {
+        sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE batch_id = ? AND deleted_at IS NULL AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?) ORDER BY created_at, id"#,
+            &batch_id,
+            access.private,
+            access.unpublished
         )
+        .fetch_all(pool)
+        .await
     })?;
 
-    let note_response = serde_json::json!({
-        "status": "success",
-        "data": serde_json::json!({
-            "note": to_note_response(&updated_note)
-        })
+    if notes.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "No hay notas en el lote: {}",
+            batch_id
+        )));
+    }
+
+    let note_responses = notes
+        .iter()
+        .map(|note| to_note_response(&data.config, note))
+        .collect::<Vec<NoteModelResponse>>();
+
+    let json_response = serde_json::json!({
+        "status": "ok",
+        "batch_id": batch_id,
+        "count": note_responses.len(),
+        "notes": note_responses
     });
 
-    Ok(Json(note_response))
+    Ok(Json(json_response))
 }
 
-pub async fn delete_note_handler(
-    Path(id): Path<String>,
+pub async fn upcoming_reminders_handler(
+    opts: Option<Query<FilterOptions>>,
+    headers: HeaderMap,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    
-    let query_result = sqlx::query!(r#"DELETE FROM notes WHERE id = ?"#, &id)
-        .execute(&data.db)
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let pagination = pagination(&opts)?;
+    let access = Access::of(&data.config, &headers);
+
+    let notes = read_with_fallback!(data, |pool| {
+        sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE remind_at IS NOT NULL AND reminder_fired_at IS NULL AND deleted_at IS NULL AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?) ORDER BY remind_at, id LIMIT ? OFFSET ?"#,
+            access.private,
+            access.unpublished,
+            pagination.limit as i64,
+            pagination.offset as i64
+        )
+        .fetch_all(pool)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "status": "error",
-                    "message": format!("{:?}", e)
-                })),
-            )
-        })?;
+    })?;
 
-    
-    if query_result.rows_affected() == 0 {
-        let error_response = serde_json::json!({
-            "status": "error",
-            "message": format!("La nota con el ID: {} no encontrado", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    let total = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS count FROM notes WHERE remind_at IS NOT NULL AND reminder_fired_at IS NULL AND deleted_at IS NULL AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?)"#,
+            access.private,
+            access.unpublished
+        )
+        .fetch_one(pool)
+        .await
+    })?;
+
+    let include_age = opts.include_age.unwrap_or(false);
+    let note_responses = notes
+        .iter()
+        .map(|note| with_age(to_note_response(&data.config, note), include_age))
+        .collect::<Vec<NoteModelResponse>>();
+
+    Ok(paginated_response(
+        &data.config,
+        "reminders",
+        "/api/notes/reminders/upcoming",
+        &pagination,
+        total,
+        note_responses,
+        serde_json::json!({
+            "page": pagination.page,
+            "limit": pagination.limit,
+            "include_age": include_age
+        }),
+    ))
+}
+
+pub async fn incomplete_note_list_handler(
+    opts: Option<Query<FilterOptions>>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let pagination = pagination(&opts)?;
+    let access = Access::of(&data.config, &headers);
+
+    let notes = read_with_fallback!(data, |pool| {
+        let mut query = sqlx::QueryBuilder::<MySql>::new("SELECT * FROM notes WHERE ");
+        push_incomplete_filter(&mut query, &data.config);
+        push_access_filter(&mut query, access);
+        query.push(" ORDER BY updated_at DESC, id LIMIT ");
+        query.push_bind(pagination.limit as i64);
+        query.push(" OFFSET ");
+        query.push_bind(pagination.offset as i64);
+        query.build_query_as::<NoteModel>().fetch_all(pool).await
+    })?;
+
+    let total: i64 = read_with_fallback!(data, |pool| {
+        let mut count_query = sqlx::QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM notes WHERE ");
+        push_incomplete_filter(&mut count_query, &data.config);
+        push_access_filter(&mut count_query, access);
+        count_query.build_query_scalar().fetch_one(pool).await
+    })?;
+
+    let note_responses = notes
+        .iter()
+        .map(|note| to_note_response(&data.config, note))
+        .collect::<Vec<NoteModelResponse>>();
+
+    Ok(paginated_response(
+        &data.config,
+        "incomplete",
+        "/api/notes/incomplete",
+        &pagination,
+        total,
+        note_responses,
+        serde_json::json!({
+            "page": pagination.page,
+            "limit": pagination.limit
+        }),
+    ))
+}
+
+// Renames legacy body fields from FIELD_ALIASES before the body is parsed.
+// When a payload carries both names, the canonical field wins.
+fn with_field_aliases(config: &Config, mut body: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = body.as_object_mut() {
+        for (alias, field) in &config.field_aliases {
+            if let Some(value) = object.remove(alias) {
+                if !object.contains_key(field) {
+                    object.insert(field.clone(), value);
+                }
+            }
+        }
     }
+    body
+}
 
-    Ok(StatusCode::OK)
+fn parse_note_body<T: serde::de::DeserializeOwned>(
+    config: &Config,
+    body: serde_json::Value,
+) -> Result<T, AppError> {
+    serde_json::from_value(with_field_aliases(config, body))
+        .map_err(|e| AppError::BadRequest(format!("Cuerpo invalido: {}", e)))
 }
 
+// Shared by the main list and the ids endpoint so both see the same notes.
+// Archived notes are left out of both.
+// `%`, `_` and `\\` in the search term are escaped so they match literally.
+// With the fuzzy-search feature each word only has to appear somewhere in the
+// note, in any order, instead of the whole term as one substring.
+fn push_list_filter(
+    query: &mut sqlx::QueryBuilder<'_, MySql>,
+    search: Option<&str>,
+    is_published: Option<bool>,
+    access: Access,
+    features: &Features,
+) {
+    query.push(" WHERE visibility <> 'unlisted' AND archived_at IS NULL AND deleted_at IS NULL");
+    push_access_filter(query, access);
 
-fn to_note_response(note: &NoteModel) -> NoteModelResponse {
-    NoteModelResponse {
+    if let Some(is_published) = is_published {
+        query.push(" AND is_published = ");
+        query.push_bind(is_published);
+    }
+
+    if let Some(search) = search {
+        let terms = if features.enabled(FUZZY_SEARCH) {
+            search.split_whitespace().collect()
+        } else {
+            vec![search]
+        };
+
+        for term in terms {
+            let escaped = term
+                .to_lowercase()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            let pattern = format!("%{}%", escaped);
+            query.push(" AND (LOWER(title) LIKE ");
+            query.push_bind(pattern.clone());
+            query.push(" OR LOWER(content) LIKE ");
+            query.push_bind(pattern);
+            query.push(")");
+        }
+    }
+}
+
+// Last-Modified looks at every note, not only the ones the filter keeps: a
+// note that leaves the set (archived, unpublished, made unlisted or deleted)
+// changes the page without touching the rows still in it. Archiving and
+// firing a reminder keep updated_at on purpose, so their own timestamps count
+// as well, and so does the latest delete.
+fn list_summary_query<'a>(
+    search: Option<&str>,
+    is_published: Option<bool>,
+    access: Access,
+    features: &Features,
+) -> sqlx::QueryBuilder<'a, MySql> {
+    let mut query = sqlx::QueryBuilder::<MySql>::new(
+        "SELECT COUNT(*) AS total, (SELECT MAX(updated_at) FROM notes) AS last_updated, (SELECT MAX(archived_at) FROM notes) AS last_archived, (SELECT MAX(reminder_fired_at) FROM notes) AS last_fired, (SELECT MAX(deleted_at) FROM notes) AS last_deleted FROM notes",
+    );
+    push_list_filter(&mut query, search, is_published, access, features);
+    query
+}
+
+fn push_incomplete_filter(query: &mut sqlx::QueryBuilder<'_, MySql>, config: &Config) {
+    query.push("deleted_at IS NULL AND (TRIM(title) = '' OR CHAR_LENGTH(TRIM(content)) < ");
+    query.push_bind(config.incomplete_min_content_length as i64);
+
+    if !config.incomplete_placeholder_titles.is_empty() {
+        query.push(" OR LOWER(TRIM(title)) IN (");
+        let mut separated = query.separated(", ");
+        for title in &config.incomplete_placeholder_titles {
+            separated.push_bind(title.clone());
+        }
+        separated.push_unseparated(")");
+    }
+    query.push(")");
+}
+
+pub async fn validate_note_handler(
+    State(data): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> impl IntoResponse {
+    let body = with_field_aliases(&data.config, body);
+    let errors = match serde_json::from_value::<CreateNoteSchema>(body) {
+        Ok(mut note) => {
+            note.trim();
+            note.content = note.content.or_else(|| data.config.default_content.clone());
+            note.title = note.title.or_else(|| {
+                data.config.title_template.as_deref().map(|template| {
+                    render_title_template(template, 1, &uuid::Uuid::nil().to_string())
+                })
+            });
+            let mut errors = note.validate(data.config.max_content_bytes);
+            if let Err(AppError::Unprocessable(message)) =
+                ensure_text_content(&data.config, note.content.as_deref())
+            {
+                errors.push(FieldError {
+                    field: "content",
+                    message,
+                });
+            }
+            errors
+        }
+        Err(e) => vec![FieldError {
+            field: "body",
+            message: e.to_string(),
+        }],
+    };
+
+    let json_response = if errors.is_empty() {
+        serde_json::json!({ "valid": true })
+    } else {
+        serde_json::json!({
+            "valid": false,
+            "errors": errors
+        })
+    };
+
+    Json(json_response)
+}
+
+pub async fn note_count_handler(
+    opts: Option<Query<CountOptions>>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let approximate = opts.approximate.unwrap_or(false);
+
+    // InnoDB only keeps an estimate in TABLE_ROWS, but reading it does not
+    // scan the table the way COUNT(*) does.
+    let count = if approximate {
+        read_with_fallback!(data, |pool| {
+            sqlx::query_scalar::<_, Option<u64>>(
+                r#"SELECT TABLE_ROWS FROM information_schema.TABLES WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'notes'"#,
+            )
+            .fetch_optional(pool)
+            .await
+        })
+        .map(|rows| rows.flatten().unwrap_or(0) as i64)
+    } else {
+        read_with_fallback!(data, |pool| {
+            sqlx::query_scalar!(r#"SELECT COUNT(*) AS count FROM notes WHERE deleted_at IS NULL"#)
+                .fetch_one(pool)
+                .await
+        })
+    }?;
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "count": count,
+            "approximate": approximate
+        })
+    });
+
+    Ok(with_cache_policy(
+        &data.config,
+        "count",
+        Json(json_response),
+    ))
+}
+
+pub async fn note_stats_handler(
+    opts: Option<Query<StatsOptions>>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let window = opts.window.unwrap_or_else(|| "7d".to_string());
+
+    let duration = match parse_window(&window) {
+        Some(duration) => duration,
+        None => {
+            return Err(AppError::BadRequest(format!(
+                "Ventana de tiempo invalida: {}. Use formatos como 24h, 7d o 30d",
+                window
+            )));
+        }
+    };
+    let since = chrono::Utc::now() - duration;
+
+    let created = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS count FROM notes WHERE created_at >= ? AND deleted_at IS NULL"#,
+            since
+        )
+        .fetch_one(pool)
+        .await
+    })?;
+
+    let updated = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS count FROM notes WHERE updated_at >= ? AND updated_at > created_at AND deleted_at IS NULL"#,
+            since
+        )
+        .fetch_one(pool)
+        .await
+    })?;
+
+    let deleted = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS count FROM notes WHERE deleted_at >= ?"#,
+            since
+        )
+        .fetch_one(pool)
+        .await
+    })?;
+
+    let by_status = read_with_fallback!(data, |pool| {
+        sqlx::query_as::<_, (bool, i64)>(
+            r#"SELECT is_published, COUNT(*) FROM notes WHERE deleted_at IS NULL GROUP BY is_published"#,
+        )
+        .fetch_all(pool)
+        .await
+    })?;
+    let (published, unpublished) =
+        by_status
+            .iter()
+            .fold((0, 0), |(published, unpublished), (is_published, count)| {
+                if *is_published {
+                    (published + count, unpublished)
+                } else {
+                    (published, unpublished + count)
+                }
+            });
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "window": window,
+            "since": since,
+            "created": created,
+            "updated": updated,
+            "deleted": deleted,
+            "total": published + unpublished,
+            "published": published,
+            "unpublished": unpublished
+        })
+    });
+
+    Ok(with_cache_policy(
+        &data.config,
+        "stats",
+        Json(json_response),
+    ))
+}
+
+const POLL_BATCH_SIZE: i64 = 100;
+const POLL_DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+// Cursors are "<updated_at secs>_<id>" and the feed is ordered by that pair,
+// so a client resuming from a cursor never sees the same change twice.
+pub async fn poll_notes_handler(
+    opts: Option<Query<PollOptions>>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+    let access = Access::of(&data.config, &headers);
+
+    let timeout = match opts.timeout.as_deref() {
+        Some(value) => parse_poll_timeout(value, data.config.poll_max_timeout_secs),
+        None => Some(std::time::Duration::from_secs(
+            POLL_DEFAULT_TIMEOUT_SECS.min(data.config.poll_max_timeout_secs),
+        )),
+    };
+    let Some(timeout) = timeout else {
+        return Err(AppError::BadRequest(format!(
+            "Timeout invalido; use segundos como 30s, hasta {}s",
+            data.config.poll_max_timeout_secs
+        )));
+    };
+
+    // Held until the response is sent or the client goes away, whichever
+    // comes first, since dropping the handler future releases it.
+    let Ok(_slot) = data.poll_slots.try_acquire() else {
+        return Err(AppError::Unavailable(
+            "Demasiadas esperas abiertas, intente de nuevo mas tarde".to_string(),
+        ));
+    };
+
+    // Subscribe before the first read so a write landing in between still
+    // wakes this request up.
+    let mut receiver = data.events.subscribe();
+
+    let (since_at, since_id) = match opts.since.as_deref() {
+        Some(since) => match parse_poll_cursor(since) {
+            Some(cursor) => cursor,
+            None => {
+                return Err(AppError::BadRequest(format!("Cursor invalido: {}", since)));
+            }
+        },
+        None => current_poll_cursor(&data, access).await?,
+    };
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut notes = changes_since(&data, since_at, &since_id, access).await?;
+    // Events this feed never reports (reminders, archiving, bulk updates,
+    // unlisted notes) wake it too, so it keeps waiting until a change it can
+    // report turns up or the timeout runs out.
+    while notes.is_empty() {
+        match tokio::time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Ok(_) | Err(broadcast::error::RecvError::Lagged(_))) => {
+                notes = changes_since(&data, since_at, &since_id, access).await?;
+            }
+            Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => break,
+        }
+    }
+
+    let cursor = match notes.last() {
+        Some(note) => poll_cursor(note.updated_at.unwrap_or_default(), &note.id),
+        None => poll_cursor(since_at, &since_id),
+    };
+    let (deleted, live): (Vec<NoteModel>, Vec<NoteModel>) = notes
+        .into_iter()
+        .partition(|note| note.deleted_at.is_some());
+    let note_responses = live
+        .iter()
+        .map(|note| to_note_response(&data.config, note))
+        .collect::<Vec<NoteModelResponse>>();
+    let deleted = deleted
+        .into_iter()
+        .map(|note| note.id)
+        .collect::<Vec<String>>();
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "cursor": cursor,
+            "count": note_responses.len(),
+            "changes": note_responses,
+            "deleted": deleted
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+// Soft-deleted rows come back too, as tombstones: deleting or merging a note
+// away bumps its updated_at like any other change. Notes NOTE_CAP evicts
+// leave no row behind and are not reported.
+async fn changes_since(
+    data: &AppState,
+    since_at: chrono::DateTime<chrono::Utc>,
+    since_id: &str,
+    access: Access,
+) -> Result<Vec<NoteModel>, AppError> {
+    read_with_fallback!(data, |pool| {
+        sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE visibility <> 'unlisted' AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?) AND (updated_at > ? OR (updated_at = ? AND id > ?)) ORDER BY updated_at, id LIMIT ?"#,
+            access.private,
+            access.unpublished,
+            since_at,
+            since_at,
+            since_id,
+            POLL_BATCH_SIZE
+        )
+        .fetch_all(pool)
+        .await
+    })
+    .map_err(AppError::from)
+}
+
+async fn current_poll_cursor(
+    data: &AppState,
+    access: Access,
+) -> Result<(chrono::DateTime<chrono::Utc>, String), AppError> {
+    let latest = read_with_fallback!(data, |pool| {
+        sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE visibility <> 'unlisted' AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?) ORDER BY updated_at DESC, id DESC LIMIT 1"#,
+            access.private,
+            access.unpublished
+        )
+        .fetch_optional(pool)
+        .await
+    })?;
+
+    Ok(match latest {
+        Some(note) => (note.updated_at.unwrap_or_default(), note.id),
+        None => (chrono::DateTime::<chrono::Utc>::default(), String::new()),
+    })
+}
+
+fn poll_cursor(updated_at: chrono::DateTime<chrono::Utc>, id: &str) -> String {
+    format!("{}_{}", updated_at.timestamp(), id)
+}
+
+fn parse_poll_cursor(cursor: &str) -> Option<(chrono::DateTime<chrono::Utc>, String)> {
+    let (secs, id) = cursor.split_once('_')?;
+    let updated_at = chrono::DateTime::from_timestamp(secs.parse().ok()?, 0)?;
+    Some((updated_at, id.to_string()))
+}
+
+fn parse_poll_timeout(value: &str, max_secs: u64) -> Option<std::time::Duration> {
+    let value = value.trim();
+    let secs: u64 = value.strip_suffix('s').unwrap_or(value).parse().ok()?;
+    (secs <= max_secs).then(|| std::time::Duration::from_secs(secs))
+}
+
+// Notes created inside [from, to) are reported as created, notes created
+// before `from` and modified inside the range as updated, and notes created
+// before `from` and soft-deleted inside the range as deleted.
+pub async fn note_delta_handler(
+    Query(opts): Query<DeltaOptions>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    if opts.from >= opts.to {
+        return Err(AppError::BadRequest(
+            "El parametro from debe ser anterior a to".to_string(),
+        ));
+    }
+
+    let created = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT id FROM notes WHERE created_at >= ? AND created_at < ? AND deleted_at IS NULL ORDER BY created_at, id"#,
+            opts.from,
+            opts.to
+        )
+        .fetch_all(pool)
+        .await
+    })?;
+
+    let updated = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT id FROM notes WHERE created_at < ? AND updated_at >= ? AND updated_at < ? AND deleted_at IS NULL ORDER BY updated_at, id"#,
+            opts.from,
+            opts.from,
+            opts.to
+        )
+        .fetch_all(pool)
+        .await
+    })?;
+
+    let deleted = read_with_fallback!(data, |pool| {
+        sqlx::query_scalar!(
+            r#"SELECT id FROM notes WHERE created_at < ? AND deleted_at >= ? AND deleted_at < ? ORDER BY deleted_at, id"#,
+            opts.from,
+            opts.from,
+            opts.to
+        )
+        .fetch_all(pool)
+        .await
+    })?;
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "from": opts.from,
+            "to": opts.to,
+            "created": created,
+            "updated": updated,
+            "deleted": deleted
+        })
+    });
+
+    Ok(with_cache_policy(
+        &data.config,
+        "delta",
+        Json(json_response),
+    ))
+}
+
+pub async fn create_note_handler(
+    State(data): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut body: CreateNoteSchema = parse_note_body(&data.config, body)?;
+    body.trim();
+    body.content = body.content.or_else(|| data.config.default_content.clone());
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let title = title_or_template(&data, body.title.take(), &id).await?;
+    body.title = title.map(|title| normalize_title(&data.config, title));
+
+    let errors = body.validate(data.config.max_content_bytes);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    ensure_text_content(&data.config, body.content.as_deref())?;
+    let title = body.title.unwrap_or_default();
+    let content = body.content.unwrap_or_default();
+
+    let visibility = body.visibility.unwrap_or_default();
+    let mut tx = data.db.begin().await?;
+    // Both stamps come from the same CURRENT_TIMESTAMP, so a new note never
+    // looks edited.
+    let query_result = sqlx::query(
+        r#"INSERT INTO notes (id, title, content, is_published, visibility, remind_at, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)"#,
+    )
+    .bind(&id)
+    .bind(&title)
+    .bind(&content)
+    .bind(body.is_published.unwrap_or(false))
+    .bind(visibility.as_str())
+    .bind(body.remind_at)
+    .execute(&mut *tx)
+    .await;
+
+    if let Err(err) = query_result {
+        if is_duplicate_entry(&err) {
+            return Err(AppError::Conflict("Note already exists".to_string()));
+        }
+
+        return Err(AppError::from(err));
+    }
+    let evicted = enforce_note_cap(&data.config, &mut tx, std::slice::from_ref(&id)).await?;
+
+    let note = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    publish_evicted(&data, evicted);
+
+    let note_response = serde_json::json!({
+            "status": "success",
+            "data": serde_json::json!({
+                "note": to_note_response(&data.config, &note)
+        })
+    });
+
+    data.publish(NoteEvent::Created {
+        note: to_note_response(&data.config, &note),
+    });
+
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, format!("/api/notes/{}", id))],
+        Json(note_response),
+    ))
+}
+
+const MAX_BATCH_CREATE_NOTES: usize = 100;
+
+// All or nothing, unlike import: the notes go in one transaction, and an
+// invalid note or a taken title rolls the whole batch back.
+pub async fn batch_create_notes_handler(
+    State(data): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<Vec<serde_json::Value>>,
+) -> Result<impl IntoResponse, AppError> {
+    if body.is_empty() || body.len() > MAX_BATCH_CREATE_NOTES {
+        return Err(AppError::BadRequest(format!(
+            "Se requieren entre 1 y {} notas por lote",
+            MAX_BATCH_CREATE_NOTES
+        )));
+    }
+
+    let mut notes = Vec::with_capacity(body.len());
+    for (index, note) in body.into_iter().enumerate() {
+        let mut note: CreateNoteSchema = parse_note_body(&data.config, note)?;
+        note.trim();
+        note.content = note.content.or_else(|| data.config.default_content.clone());
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let title = title_or_template(&data, note.title.take(), &id).await?;
+        note.title = title.map(|title| normalize_title(&data.config, title));
+
+        let errors = note.validate(data.config.max_content_bytes);
+        if !errors.is_empty() {
+            let messages = errors
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect::<Vec<&str>>();
+            return Err(AppError::BadRequest(format!(
+                "La nota {} del lote no es valida: {}",
+                index,
+                messages.join("; ")
+            )));
+        }
+        ensure_text_content(&data.config, note.content.as_deref())?;
+        notes.push((id, note));
+    }
+
+    // Shared by the whole request, so GET /api/notes/batch/:batch_id lists
+    // exactly the notes it created.
+    let batch_id = uuid::Uuid::new_v4().to_string();
+    let mut tx = data.db.begin().await?;
+
+    for (id, note) in &notes {
+        let title = note.title.as_deref().unwrap_or_default();
+        let query_result = sqlx::query(
+            r#"INSERT INTO notes (id, title, content, is_published, visibility, remind_at, batch_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)"#,
+        )
+        .bind(id)
+        .bind(title)
+        .bind(note.content.as_deref().unwrap_or_default())
+        .bind(note.is_published.unwrap_or(false))
+        .bind(note.visibility.unwrap_or_default().as_str())
+        .bind(note.remind_at)
+        .bind(&batch_id)
+        .execute(&mut *tx)
+        .await;
+
+        if let Err(err) = query_result {
+            if is_duplicate_entry(&err) {
+                return Err(AppError::Conflict(format!(
+                    "Ya existe una nota con el titulo: {}",
+                    title
+                )));
+            }
+
+            return Err(AppError::from(err));
+        }
+    }
+    let ids = notes
+        .iter()
+        .map(|(id, _)| id.clone())
+        .collect::<Vec<String>>();
+    let evicted = enforce_note_cap(&data.config, &mut tx, &ids).await?;
+
+    let mut created = Vec::with_capacity(notes.len());
+    for (id, _) in &notes {
+        let note = sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        created.push(to_note_response(&data.config, &note));
+    }
+
+    tx.commit().await?;
+
+    publish_evicted(&data, evicted);
+    for note in &created {
+        data.publish(NoteEvent::Created { note: note.clone() });
+    }
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "batch_id": batch_id,
+            "count": created.len(),
+            "notes": created
+        })
+    });
+
+    Ok((StatusCode::CREATED, Json(json_response)))
+}
+
+pub async fn get_note_handler(
+    Path(id): Path<String>,
+    opts: Option<Query<NoteOptions>>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_uuid(&id)?;
+    let Query(opts) = opts.unwrap_or_default();
+    
+    let query_result = read_with_fallback!(data, |pool| {
+        sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+            &id
+        )
+        .fetch_one(pool)
+        .await
+    });
+
+    
+    match query_result {
+        Ok(note) if !hidden_from_caller(&data.config, &headers, &note) => {
+            let include_age = opts.include_age.unwrap_or(false);
+            let note_response = serde_json::json!({
+                "status": "success",
+                "data": serde_json::json!({
+                    "note": with_age(to_note_response(&data.config, &note), include_age)
+                })
+            });
+
+            Ok(with_cache_policy(&data.config, "note", Json(note_response)))
+        }
+        Ok(_) => Err(AppError::NotFound(format!(
+            "La nota con el ID: {} no encontrado",
+            id
+        ))),
+        Err(sqlx::Error::RowNotFound) if data.config.report_deleted_notes => {
+            let deleted = sqlx::query!(
+                r#"SELECT is_published AS "is_published: bool", visibility FROM notes WHERE id = ? AND deleted_at IS NOT NULL"#,
+                &id
+            )
+            .fetch_optional(&data.db)
+            .await?;
+            let access = Access::of(&data.config, &headers);
+            Err(missing_note_error(
+                &id,
+                deleted.map(|note| (note.is_published, note.visibility)),
+                access,
+            ))
+        }
+        Err(sqlx::Error::RowNotFound) => Err(AppError::NotFound(format!(
+            "La nota con el ID: {} no encontrado",
+            id
+        ))),
+        Err(e) => Err(AppError::from(e)),
+    }
+}
+
+// `deleted` is the soft-deleted row behind `id`, if any. A note the caller
+// could not have read while live still answers 404, so the 410 tells nothing
+// new about it.
+fn missing_note_error(id: &str, deleted: Option<(bool, String)>, access: Access) -> AppError {
+    match deleted {
+        Some((is_published, visibility)) if !access.hides(is_published, &visibility) => {
+            AppError::NoteDeleted(format!("La nota con el ID: {} fue eliminada", id))
+        }
+        _ => AppError::NotFound(format!("La nota con el ID: {} no encontrado", id)),
+    }
+}
+
+pub async fn export_note_markdown_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_uuid(&id)?;
+
+    let query_result = read_with_fallback!(data, |pool| {
+        sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+            &id
+        )
+        .fetch_one(pool)
+        .await
+    });
+
+    let note = match query_result {
+        Ok(note) if !hidden_from_caller(&data.config, &headers, &note) => note,
+        Ok(_) | Err(sqlx::Error::RowNotFound) => {
+            return Err(AppError::NotFound(format!(
+                "La nota con el ID: {} no encontrado",
+                id
+            )));
+        }
+        Err(e) => {
+            return Err(AppError::from(e));
+        }
+    };
+
+    let note = to_note_response(&data.config, &note);
+    // JSON strings are valid YAML scalars, which saves escaping titles by hand.
+    let markdown = format!(
+        "---\ntitle: {}\nvisibility: {}\npublished: {}\ncreated_at: {}\nupdated_at: {}\n---\n\n{}\n",
+        serde_json::to_string(&note.title).unwrap_or_default(),
+        note.visibility,
+        note.is_published,
+        note.created_at.to_rfc3339(),
+        note.updated_at.to_rfc3339(),
+        note.content
+    );
+
+    Ok((
+        [
+            (CONTENT_TYPE, "text/markdown; charset=utf-8".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.md\"", note.id),
+            ),
+        ],
+        markdown,
+    ))
+}
+
+pub async fn edit_note_handler(
+    Path(id): Path<String>,
+    opts: Option<Query<EditNoteOptions>>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_uuid(&id)?;
+    let Query(opts) = opts.unwrap_or_default();
+    let mut body: UpdateNoteSchema = parse_note_body(&data.config, body)?;
+    body.trim();
+    let errors = body.validate(data.config.max_content_bytes);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    ensure_text_content(
+        &data.config,
+        body.content.as_ref().and_then(Option::as_deref),
+    )?;
+   
+    let query_result = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&data.db)
+    .await;
+
+    
+    let note = match query_result {
+        Ok(note) if !hidden_from_caller(&data.config, &headers, &note) => note,
+        Ok(_) => {
+            return Err(AppError::NotFound(format!(
+                "La nota con el ID: {} no encontrado",
+                id
+            )));
+        }
+        Err(sqlx::Error::RowNotFound) if opts.upsert.unwrap_or(false) => {
+            return insert_note_with_id(&data, id, body).await;
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(AppError::NotFound(format!(
+                "La nota con el ID: {} no encontrado",
+                id
+            )));
+        }
+        Err(e) => {
+            return Err(AppError::from(e));
+        }
+    };
+
+    ensure_not_locked(&data, &id, &headers).await?;
+   
+    let is_published = body.is_published.unwrap_or(note.is_published);
+
+    let content = match body.content {
+        Some(content) => content.unwrap_or_default(),
+        None => note.content,
+    };
+    let visibility = match body.visibility {
+        Some(visibility) => visibility.as_str().to_string(),
+        None => note.visibility,
+    };
+    // A new or cleared remind_at re-arms the reminder.
+    let (remind_at, reminder_fired_at) = match body.remind_at {
+        Some(remind_at) => (remind_at, None),
+        None => (note.remind_at, note.reminder_fired_at),
+    };
+
+    
+    let update_result = sqlx::query(
+        r#"UPDATE notes SET title = ?, content = ?, is_published = ?, visibility = ?, remind_at = ?, reminder_fired_at = ?, archived_at = NULL WHERE id = ? AND deleted_at IS NULL"#,
+    )
+    .bind(
+        body.title
+            .map(|title| normalize_title(&data.config, title))
+            .unwrap_or(note.title),
+    )
+    .bind(&content)
+    .bind(is_published)
+    .bind(&visibility)
+    .bind(remind_at)
+    .bind(reminder_fired_at)
+    .bind(&id)
+    .execute(&data.db)
+    .await?;
+
+    
+    if update_result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "La nota con el ID: {} no encontrado",
+            id
+        )));
+    }
+
+    let updated_note = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&data.db)
+    .await?;
+
+    let note_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": to_note_response(&data.config, &updated_note)
+        })
+    });
+
+    data.publish(NoteEvent::Updated {
+        note: to_note_response(&data.config, &updated_note),
+    });
+
+    Ok((StatusCode::OK, Json(note_response)))
+}
+
+async fn insert_note_with_id(
+    data: &AppState,
+    id: String,
+    body: UpdateNoteSchema,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let is_published = body.is_published.unwrap_or(false);
+    let visibility = body.visibility.unwrap_or_default();
+    let (Some(title), Some(Some(content))) = (body.title, body.content) else {
+        return Err(AppError::BadRequest(
+            "Para crear la nota se requieren los campos title y content".to_string(),
+        ));
+    };
+    let title = normalize_title(&data.config, title);
+
+    let mut tx = data.db.begin().await?;
+    let query_result = sqlx::query(
+        r#"INSERT INTO notes (id, title, content, is_published, visibility, remind_at, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)"#,
+    )
+    .bind(&id)
+    .bind(&title)
+    .bind(&content)
+    .bind(is_published)
+    .bind(visibility.as_str())
+    .bind(body.remind_at.flatten())
+    .execute(&mut *tx)
+    .await;
+
+    if let Err(err) = query_result {
+        if is_duplicate_entry(&err) {
+            return Err(AppError::Conflict("Note already exists".to_string()));
+        }
+
+        return Err(AppError::from(err));
+    }
+    let evicted = enforce_note_cap(&data.config, &mut tx, std::slice::from_ref(&id)).await?;
+
+    let note = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    publish_evicted(data, evicted);
+
+    let note_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": to_note_response(&data.config, &note)
+        })
+    });
+
+    data.publish(NoteEvent::Created {
+        note: to_note_response(&data.config, &note),
+    });
+
+    Ok((StatusCode::CREATED, Json(note_response)))
+}
+
+pub async fn autosave_note_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+    ApiJson(mut body): ApiJson<AutosaveNoteSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_uuid(&id)?;
+    body.trim();
+    let errors = body.validate(data.config.max_content_bytes);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    ensure_not_locked(&data, &id, &headers).await?;
+    ensure_text_content(&data.config, Some(&body.content))?;
+
+    // updated_at tiene precision de segundos; se fuerza a avanzar para que dos
+    // guardados en el mismo segundo no compartan la misma version.
+    let access = Access::of(&data.config, &headers);
+    let update_result = sqlx::query(
+        r#"UPDATE notes SET content = ?, archived_at = NULL, updated_at = GREATEST(CURRENT_TIMESTAMP, updated_at + INTERVAL 1 SECOND) WHERE id = ? AND updated_at = ? AND deleted_at IS NULL AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?)"#,
+    )
+    .bind(&body.content)
+    .bind(&id)
+    .bind(body.updated_at)
+    .bind(access.private)
+    .bind(access.unpublished)
+    .execute(&data.db)
+    .await?;
+
+    if update_result.rows_affected() == 0 {
+        let query_result = sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+            &id
+        )
+        .fetch_one(&data.db)
+        .await;
+
+        return match query_result {
+            Ok(note) if !access.hides(note.is_published, &note.visibility) => Err(AppError::Stale(
+                Box::new(to_note_response(&data.config, &note)),
+            )),
+            Ok(_) | Err(sqlx::Error::RowNotFound) => Err(AppError::NotFound(format!(
+                "La nota con el ID: {} no encontrado",
+                id
+            ))),
+            Err(e) => Err(AppError::from(e)),
+        };
+    }
+
+    let note = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&data.db)
+    .await?;
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "id": id,
+            "updated_at": note.updated_at
+        })
+    });
+
+    data.publish(NoteEvent::Updated {
+        note: to_note_response(&data.config, &note),
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn lock_note_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_uuid(&id)?;
+    let holder = required_lock_holder(&headers)?;
+
+    let note_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS count FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&data.db)
+    .await?;
+
+    if note_count == 0 {
+        return Err(AppError::NotFound(format!(
+            "La nota con el ID: {} no encontrado",
+            id
+        )));
+    }
+
+    sqlx::query!(
+        r#"DELETE FROM note_locks WHERE note_id = ? AND expires_at <= CURRENT_TIMESTAMP"#,
+        &id
+    )
+    .execute(&data.db)
+    .await?;
+
+    // Si el bloqueo ya existe solo se renueva cuando pertenece al mismo editor.
+    let ttl = data.config.note_lock_ttl_secs;
+    sqlx::query!(
+        r#"INSERT INTO note_locks (note_id, holder, expires_at) VALUES (?, ?, CURRENT_TIMESTAMP + INTERVAL ? SECOND) ON DUPLICATE KEY UPDATE expires_at = IF(holder = ?, CURRENT_TIMESTAMP + INTERVAL ? SECOND, expires_at)"#,
+        &id,
+        &holder,
+        ttl,
+        &holder,
+        ttl
+    )
+    .execute(&data.db)
+    .await?;
+
+    let lock = sqlx::query_as!(
+        NoteLockModel,
+        r#"SELECT * FROM note_locks WHERE note_id = ?"#,
+        &id
+    )
+    .fetch_one(&data.db)
+    .await?;
+
+    if lock.holder != holder {
+        return Err(AppError::Locked(lock));
+    }
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "lock": lock
+        })
+    });
+
+    Ok(Json(json_response))
+}
+
+pub async fn unlock_note_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_uuid(&id)?;
+    let holder = required_lock_holder(&headers)?;
+
+    sqlx::query!(
+        r#"DELETE FROM note_locks WHERE note_id = ? AND (holder = ? OR expires_at <= CURRENT_TIMESTAMP)"#,
+        &id,
+        &holder
+    )
+    .execute(&data.db)
+    .await?;
+
+    ensure_not_locked(&data, &id, &headers).await?;
+
+    Ok(StatusCode::OK)
+}
+
+const IMPORT_BATCH_SIZE: usize = 100;
+
+pub async fn import_notes_handler(
+    opts: Option<Query<ImportOptions>>,
+    State(data): State<Arc<AppState>>,
+    body: Body,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(opts) = opts.unwrap_or_default();
+
+    if opts.format.as_deref() != Some("ndjson") {
+        return Err(AppError::BadRequest(
+            "Formato de importacion no soportado, use format=ndjson".to_string(),
+        ));
+    }
+
+    let mut stream = body.into_data_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut batch: Vec<(usize, CreateNoteSchema)> = Vec::new();
+    let mut failed: Vec<serde_json::Value> = Vec::new();
+    let mut skipped: Vec<serde_json::Value> = Vec::new();
+    let dedupe = opts.dedupe.unwrap_or(false);
+    let mut line_number = 0;
+    let mut imported: Vec<String> = Vec::new();
+    // Shared by every note this request imports, across insert batches.
+    let batch_id = uuid::Uuid::new_v4().to_string();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk =
+            chunk.map_err(|e| AppError::BadRequest(format!("Error leyendo el cuerpo: {}", e)))?;
+        buffer.extend_from_slice(&chunk);
+
+        while let Some(position) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=position).collect();
+            line_number += 1;
+            parse_import_line(&data.config, line_number, &line, &mut batch, &mut failed);
+
+            if batch.len() >= IMPORT_BATCH_SIZE {
+                let ids = insert_import_batch(
+                    &data,
+                    &batch_id,
+                    &mut batch,
+                    &mut failed,
+                    dedupe,
+                    &mut skipped,
+                )
+                .await?;
+                imported.extend(ids);
+            }
+        }
+    }
+
+    if !buffer.is_empty() {
+        line_number += 1;
+        parse_import_line(&data.config, line_number, &buffer, &mut batch, &mut failed);
+    }
+    if !batch.is_empty() {
+        let ids = insert_import_batch(
+            &data,
+            &batch_id,
+            &mut batch,
+            &mut failed,
+            dedupe,
+            &mut skipped,
+        )
+        .await?;
+        imported.extend(ids);
+    }
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "lines": line_number,
+            "batch_id": batch_id,
+            "imported": imported.len(),
+            "skipped": skipped,
+            "failed": failed
+        })
+    });
+
+    if !imported.is_empty() {
+        data.publish(NoteEvent::Imported { ids: imported });
+    }
+
+    Ok(Json(json_response))
+}
+
+fn parse_import_line(
+    config: &Config,
+    line_number: usize,
+    line: &[u8],
+    batch: &mut Vec<(usize, CreateNoteSchema)>,
+    failed: &mut Vec<serde_json::Value>,
+) {
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return;
+    }
+
+    let note = serde_json::from_slice::<serde_json::Value>(line).and_then(|note| {
+        serde_json::from_value::<CreateNoteSchema>(with_field_aliases(config, note))
+    });
+    match note {
+        Ok(note) => batch.push((line_number, note)),
+        Err(e) => failed.push(serde_json::json!({
+            "line": line_number,
+            "message": e.to_string()
+        })),
+    }
+}
+
+async fn insert_import_batch(
+    data: &AppState,
+    batch_id: &str,
+    batch: &mut Vec<(usize, CreateNoteSchema)>,
+    failed: &mut Vec<serde_json::Value>,
+    dedupe: bool,
+    skipped: &mut Vec<serde_json::Value>,
+) -> Result<Vec<String>, AppError> {
+    let mut tx = data.db.begin().await?;
+
+    let mut imported = Vec::new();
+    for (line_number, mut note) in batch.drain(..) {
+        note.trim();
+        note.content = note.content.or_else(|| data.config.default_content.clone());
+
+        let id = uuid::Uuid::new_v4().to_string();
+        match title_or_template(data, note.title.take(), &id).await {
+            Ok(title) => note.title = title.map(|title| normalize_title(&data.config, title)),
+            Err(e) => {
+                failed.push(serde_json::json!({
+                    "line": line_number,
+                    "message": e.to_string()
+                }));
+                continue;
+            }
+        }
+
+        let errors = note.validate(data.config.max_content_bytes);
+        if !errors.is_empty() {
+            failed.push(serde_json::json!({
+                "line": line_number,
+                "message": "La nota no es valida",
+                "errors": errors
+            }));
+            continue;
+        }
+        if let Err(AppError::Unprocessable(message)) =
+            ensure_text_content(&data.config, note.content.as_deref())
+        {
+            failed.push(serde_json::json!({
+                "line": line_number,
+                "message": message
+            }));
+            continue;
+        }
+        let title = note.title.take().unwrap_or_default();
+        let content = note.content.take().unwrap_or_default();
+
+        // Runs inside the batch transaction, so repeats within the same file
+        // are caught as well as notes that were already stored.
+        if dedupe {
+            let existing = sqlx::query_scalar!(
+                r#"SELECT id FROM notes WHERE LOWER(TRIM(title)) = LOWER(TRIM(?)) AND SHA2(content, 256) = SHA2(?, 256) AND deleted_at IS NULL LIMIT 1"#,
+                &title,
+                &content
+            )
+            .fetch_optional(&mut *tx)
+            .await;
+
+            match existing {
+                Ok(Some(existing_id)) => {
+                    skipped.push(serde_json::json!({
+                        "line": line_number,
+                        "duplicate_of": existing_id
+                    }));
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    failed.push(serde_json::json!({
+                        "line": line_number,
+                        "message": e.to_string()
+                    }));
+                    continue;
+                }
+            }
+        }
+        let is_published = note.is_published.unwrap_or(false);
+        let visibility = note.visibility.unwrap_or_default();
+        let query_result = sqlx::query(
+            r#"INSERT INTO notes (id, title, content, is_published, visibility, remind_at, batch_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)"#,
+        )
+        .bind(&id)
+        .bind(&title)
+        .bind(&content)
+        .bind(is_published)
+        .bind(visibility.as_str())
+        .bind(note.remind_at)
+        .bind(batch_id)
+        .execute(&mut *tx)
+        .await;
+
+        match query_result {
+            Ok(_) => imported.push(id),
+            Err(e) => failed.push(serde_json::json!({
+                "line": line_number,
+                "message": e.to_string()
+            })),
+        }
+    }
+    let evicted = enforce_note_cap(&data.config, &mut tx, &imported).await?;
+
+    tx.commit().await?;
+    publish_evicted(data, evicted);
+
+    Ok(imported)
+}
+
+pub async fn bulk_set_notes_handler(
+    State(data): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<BulkSetSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    if body.set.is_published.is_none() && body.set.visibility.is_none() {
+        return Err(AppError::BadRequest(
+            "Indique al menos un cambio en set (is_published o visibility)".to_string(),
+        ));
+    }
+
+    let filter_is_empty = body.filter.is_published.is_none() && body.filter.visibility.is_none();
+    if filter_is_empty && !body.confirm_all {
+        return Err(AppError::BadRequest(
+            "Un filtro vacio afecta a todas las notas; envie confirm_all=true para confirmarlo"
+                .to_string(),
+        ));
+    }
+
+    let mut query = sqlx::QueryBuilder::<MySql>::new("UPDATE notes SET ");
+    let mut changes = query.separated(", ");
+    if let Some(is_published) = body.set.is_published {
+        changes.push("is_published = ");
+        changes.push_bind_unseparated(is_published);
+    }
+    if let Some(visibility) = body.set.visibility {
+        changes.push("visibility = ");
+        changes.push_bind_unseparated(visibility.as_str());
+    }
+
+    query.push(" WHERE deleted_at IS NULL");
+    if let Some(is_published) = body.filter.is_published {
+        query.push(" AND is_published = ");
+        query.push_bind(is_published);
+    }
+    if let Some(visibility) = body.filter.visibility {
+        query.push(" AND visibility = ");
+        query.push_bind(visibility.as_str());
+    }
+
+    let result = query.build().execute(&data.db).await?;
+
+    let affected = result.rows_affected();
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "affected": affected
+        })
+    });
+
+    data.publish(NoteEvent::BulkUpdated { affected });
+
+    Ok(Json(json_response))
+}
+
+const MAX_BULK_DELETE_NOTES: usize = 100;
+
+// Soft-deletes like delete_note_handler. Ids that do not exist or were
+// already deleted are skipped, so `deleted` tells the client which ones took.
+pub async fn bulk_delete_notes_handler(
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<BulkDeleteSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut ids: Vec<String> = Vec::new();
+    for id in body.ids {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+
+    if ids.is_empty() || ids.len() > MAX_BULK_DELETE_NOTES {
+        return Err(AppError::BadRequest(format!(
+            "Se requieren entre 1 y {} IDs distintos para eliminar",
+            MAX_BULK_DELETE_NOTES
+        )));
+    }
+
+    let mut tx = data.db.begin().await?;
+
+    let mut select = sqlx::QueryBuilder::<MySql>::new(
+        "SELECT id FROM notes WHERE deleted_at IS NULL AND id IN ",
+    );
+    push_id_list(&mut select, &ids);
+    push_access_filter(&mut select, Access::of(&data.config, &headers));
+    select.push(" FOR UPDATE");
+    let found: Vec<String> = select.build_query_scalar().fetch_all(&mut *tx).await?;
+
+    // Like a single delete, notes locked by another editor are left alone;
+    // they are reported back instead of failing the whole batch.
+    let mut locked: Vec<String> = Vec::new();
+    if !found.is_empty() {
+        let mut locks = sqlx::QueryBuilder::<MySql>::new(
+            "SELECT note_id FROM note_locks WHERE expires_at > CURRENT_TIMESTAMP AND note_id IN ",
+        );
+        push_id_list(&mut locks, &found);
+        if let Some(holder) = lock_holder(&headers) {
+            locks.push(" AND holder <> ");
+            locks.push_bind(holder.to_string());
+        }
+        locked = locks.build_query_scalar().fetch_all(&mut *tx).await?;
+    }
+    let deleted: Vec<String> = found
+        .into_iter()
+        .filter(|id| !locked.contains(id))
+        .collect();
+
+    if !deleted.is_empty() {
+        let mut update = sqlx::QueryBuilder::<MySql>::new(
+            "UPDATE notes SET deleted_at = CURRENT_TIMESTAMP WHERE id IN ",
+        );
+        push_id_list(&mut update, &deleted);
+        update.build().execute(&mut *tx).await?;
+    }
+
+    tx.commit().await?;
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "requested": ids.len(),
+            "deleted": deleted.len(),
+            "ids": deleted,
+            "locked": locked
+        })
+    });
+
+    for id in deleted {
+        data.publish(NoteEvent::Deleted { id });
+    }
+
+    Ok(Json(json_response))
+}
+
+// Binds every id as its own placeholder: `(?, ?, ...)`.
+fn push_id_list(query: &mut sqlx::QueryBuilder<'_, MySql>, ids: &[String]) {
+    query.push("(");
+    let mut separated = query.separated(", ");
+    for id in ids {
+        separated.push_bind(id.clone());
+    }
+    separated.push_unseparated(")");
+}
+
+const MERGE_SEPARATOR: &str = "\n\n---\n\n";
+const MAX_MERGE_NOTES: usize = 50;
+
+pub async fn merge_notes_handler(
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<MergeNotesSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut others: Vec<String> = Vec::new();
+    for id in body.others {
+        if id != body.primary && !others.contains(&id) {
+            others.push(id);
+        }
+    }
+
+    if others.is_empty() || others.len() > MAX_MERGE_NOTES {
+        return Err(AppError::BadRequest(format!(
+            "Se requieren entre 1 y {} notas distintas de la principal para combinar",
+            MAX_MERGE_NOTES
+        )));
+    }
+
+    ensure_not_locked(&data, &body.primary, &headers).await?;
+    for id in &others {
+        ensure_not_locked(&data, id, &headers).await?;
+    }
+
+    let mut tx = data.db.begin().await?;
+
+    let mut contents = Vec::with_capacity(others.len() + 1);
+    for id in std::iter::once(&body.primary).chain(others.iter()) {
+        let query_result = sqlx::query_as!(
+            NoteModel,
+            r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL FOR UPDATE"#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await;
+
+        match query_result {
+            Ok(note) if !hidden_from_caller(&data.config, &headers, &note) => {
+                contents.push(note.content)
+            }
+            Ok(_) | Err(sqlx::Error::RowNotFound) => {
+                return Err(AppError::NotFound(format!(
+                    "La nota con el ID: {} no encontrado",
+                    id
+                )));
+            }
+            Err(e) => {
+                return Err(AppError::from(e));
+            }
+        }
+    }
+
+    // Each note fits on its own, but together they may not.
+    let merged = contents.join(MERGE_SEPARATOR);
+    let mut errors = Vec::new();
+    validate_content(&merged, data.config.max_content_bytes, &mut errors);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    sqlx::query!(
+        r#"UPDATE notes SET content = ?, archived_at = NULL WHERE id = ? AND deleted_at IS NULL"#,
+        merged,
+        &body.primary
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    for id in &others {
+        sqlx::query!(
+            r#"UPDATE notes SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND deleted_at IS NULL"#,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let merged_note = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &body.primary
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let note_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": to_note_response(&data.config, &merged_note),
+            "merged": others
+        })
+    });
+
+    data.publish(NoteEvent::Updated {
+        note: to_note_response(&data.config, &merged_note),
+    });
+    for id in others {
+        data.publish(NoteEvent::Deleted { id });
+    }
+
+    Ok(Json(note_response))
+}
+
+pub async fn feature_note_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<FeatureNoteSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    require_admin(&headers, &data.config)?;
+    ensure_uuid(&id)?;
+
+    let query_result = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&data.db)
+    .await;
+
+    let note = match query_result {
+        Ok(note) => note,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(AppError::NotFound(format!(
+                "La nota con el ID: {} no encontrado",
+                id
+            )));
+        }
+        Err(e) => {
+            return Err(AppError::from(e));
+        }
+    };
+
+    if body.featured && !note.is_published {
+        return Err(AppError::Conflict(
+            "Solo las notas publicadas pueden destacarse".to_string(),
+        ));
+    }
+
+    let featured_order = if body.featured {
+        body.featured_order
+    } else {
+        None
+    };
+
+    sqlx::query!(
+        r#"UPDATE notes SET featured = ?, featured_order = ? WHERE id = ? AND deleted_at IS NULL"#,
+        body.featured as i8,
+        featured_order,
+        &id
+    )
+    .execute(&data.db)
+    .await?;
+
+    let updated_note = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&data.db)
+    .await?;
+
+    let note_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": to_note_response(&data.config, &updated_note)
+        })
+    });
+
+    data.publish(NoteEvent::Updated {
+        note: to_note_response(&data.config, &updated_note),
+    });
+
+    Ok(Json(note_response))
+}
+
+pub async fn publish_note_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    set_published(&data, &id, &headers, true).await
+}
+
+pub async fn unpublish_note_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    set_published(&data, &id, &headers, false).await
+}
+
+// Touches only is_published, and brings an archived note back like any other
+// change does. updated_at is set explicitly because ON UPDATE does not fire
+// when the flag already had that value. Notes hidden from the caller are
+// left alone and answer 404.
+async fn set_published(
+    data: &AppState,
+    id: &str,
+    headers: &HeaderMap,
+    is_published: bool,
+) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_uuid(id)?;
+    let access = Access::of(&data.config, headers);
+
+    let update_result = sqlx::query!(
+        r#"UPDATE notes SET is_published = ?, archived_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ? AND deleted_at IS NULL AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?)"#,
+        is_published,
+        id,
+        access.private,
+        access.unpublished
+    )
+    .execute(&data.db)
+    .await?;
+
+    if update_result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "La nota con el ID: {} no encontrado",
+            id
+        )));
+    }
+
+    let updated_note = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        id
+    )
+    .fetch_one(&data.db)
+    .await?;
+
+    let note_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": to_note_response(&data.config, &updated_note)
+        })
+    });
+
+    data.publish(NoteEvent::Updated {
+        note: to_note_response(&data.config, &updated_note),
+    });
+
+    Ok(Json(note_response))
+}
+
+pub async fn delete_note_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_uuid(&id)?;
+    ensure_not_locked(&data, &id, &headers).await?;
+
+    // Soft delete: the row stays so it can be recovered, but every read
+    // skips it and its title is free for a new note.
+    let access = Access::of(&data.config, &headers);
+    let query_result = sqlx::query!(
+        r#"UPDATE notes SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND deleted_at IS NULL AND (visibility <> 'private' OR ?) AND (is_published = 1 OR ?)"#,
+        &id,
+        access.private,
+        access.unpublished
+    )
+    .execute(&data.db)
+    .await?;
+
+    
+    if query_result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "La nota con el ID: {} no encontrado",
+            id
+        )));
+    }
+
+    data.publish(NoteEvent::Deleted { id });
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn restore_note_handler(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_uuid(&id)?;
+
+    let note = sqlx::query!(
+        r#"SELECT deleted_at, is_published AS "is_published: bool", visibility FROM notes WHERE id = ?"#,
+        &id
+    )
+    .fetch_optional(&data.db)
+    .await?;
+    let access = Access::of(&data.config, &headers);
+    let deleted_at = note
+        .filter(|note| !access.hides(note.is_published, &note.visibility))
+        .map(|note| note.deleted_at);
+
+    match deleted_at {
+        None => {
+            return Err(AppError::NotFound(format!(
+                "La nota con el ID: {} no encontrado",
+                id
+            )));
+        }
+        Some(None) => {
+            return Err(AppError::Conflict(format!(
+                "La nota con el ID: {} no esta eliminada",
+                id
+            )));
+        }
+        Some(Some(_)) => {}
+    }
+
+    // The title was freed by the delete, so a live note may have taken it.
+    let query_result = sqlx::query!(
+        r#"UPDATE notes SET deleted_at = NULL, archived_at = NULL WHERE id = ? AND deleted_at IS NOT NULL"#,
+        &id
+    )
+    .execute(&data.db)
+    .await;
+
+    match query_result {
+        Ok(result) if result.rows_affected() == 0 => {
+            return Err(AppError::Conflict(format!(
+                "La nota con el ID: {} no esta eliminada",
+                id
+            )));
+        }
+        Ok(_) => {}
+        Err(err) if is_duplicate_entry(&err) => {
+            return Err(AppError::Conflict(
+                "Ya existe otra nota con el mismo titulo".to_string(),
+            ));
+        }
+        Err(err) => return Err(AppError::from(err)),
+    }
+
+    let restored_note = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE id = ? AND deleted_at IS NULL"#,
+        &id
+    )
+    .fetch_one(&data.db)
+    .await?;
+
+    let note_response = serde_json::json!({
+        "status": "success",
+        "data": serde_json::json!({
+            "note": to_note_response(&data.config, &restored_note)
+        })
+    });
+
+    data.publish(NoteEvent::Restored {
+        note: to_note_response(&data.config, &restored_note),
+    });
+
+    Ok(Json(note_response))
+}
+
+
+// The length comes from CHAR_LENGTH so it counts characters, like the title
+// limit, rather than bytes.
+fn select_notes<'a>(config: &Config, with_content_length: bool) -> sqlx::QueryBuilder<'a, MySql> {
+    let mut query = if with_content_length {
+        sqlx::QueryBuilder::new("SELECT *, CHAR_LENGTH(content) AS content_length FROM notes")
+    } else {
+        sqlx::QueryBuilder::new("SELECT * FROM notes")
+    };
+    if let Some(index) = &config.list_index_hint {
+        query.push(format!(" USE INDEX ({})", index));
+    }
+    query
+}
+
+// With NOTE_CAP, runs in the same transaction as the inserts, once `created`
+// are in. In evict mode the least recently updated notes other than those are
+// deleted for good to get back under the cap, soft-deleted ones first, like an
+// LRU cache; a failed request rolls the eviction back with the inserts.
+// Returns the live notes evicted so the caller can publish their deletes once
+// it has committed. Concurrent creates can still overshoot the cap a little.
+async fn enforce_note_cap(
+    config: &Config,
+    tx: &mut sqlx::Transaction<'_, MySql>,
+    created: &[String],
+) -> Result<Vec<String>, AppError> {
+    let Some(cap) = config.note_cap else {
+        return Ok(Vec::new());
+    };
+    if created.is_empty() {
+        return Ok(Vec::new());
+    }
+    if created.len() > cap {
+        return Err(AppError::BadRequest(format!(
+            "No se pueden crear mas de {} notas en total",
+            cap
+        )));
+    }
+
+    let total = sqlx::query_scalar!(r#"SELECT COUNT(*) AS count FROM notes"#)
+        .fetch_one(&mut **tx)
+        .await?;
+    let excess = (total as usize).saturating_sub(cap);
+    if excess == 0 {
+        return Ok(Vec::new());
+    }
+    if config.note_cap_mode == NoteCapMode::Reject {
+        return Err(AppError::Conflict(format!(
+            "Se alcanzo el limite de {} notas",
+            cap
+        )));
+    }
+
+    let mut query =
+        sqlx::QueryBuilder::<MySql>::new("SELECT id, deleted_at FROM notes WHERE id NOT IN ");
+    push_id_list(&mut query, created);
+    query.push(" ORDER BY deleted_at IS NULL, updated_at, id LIMIT ");
+    query.push_bind(excess as i64);
+    let evicted = query
+        .build_query_as::<(String, Option<chrono::DateTime<chrono::Utc>>)>()
+        .fetch_all(&mut **tx)
+        .await?;
+    if evicted.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ids = evicted
+        .iter()
+        .map(|(id, _)| id.clone())
+        .collect::<Vec<String>>();
+    let mut query = sqlx::QueryBuilder::<MySql>::new("DELETE FROM notes WHERE id IN ");
+    push_id_list(&mut query, &ids);
+    query.build().execute(&mut **tx).await?;
+
+    tracing::info!("NOTE_CAP: {} notas eliminadas para hacer sitio", ids.len());
+    Ok(evicted
+        .into_iter()
+        .filter(|(_, deleted_at)| deleted_at.is_none())
+        .map(|(id, _)| id)
+        .collect())
+}
+
+fn publish_evicted(data: &AppState, evicted: Vec<String>) {
+    for id in evicted {
+        data.publish(NoteEvent::Deleted { id });
+    }
+}
+
+const TITLE_SEQUENCE: &str = "title";
+
+// Falls back to TITLE_TEMPLATE when the client sends no title. The sequence is
+// only advanced when the template actually uses {seq}.
+async fn title_or_template(
+    data: &AppState,
+    title: Option<String>,
+    id: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    if title.is_some() {
+        return Ok(title);
+    }
+    let Some(template) = &data.config.title_template else {
+        return Ok(None);
+    };
+
+    let seq = if template.contains("{seq}") {
+        // LAST_INSERT_ID(expr) hands the new value back in the OK packet, so
+        // concurrent creates never read each other's number.
+        sqlx::query(
+            r#"INSERT INTO note_sequences (name, value) VALUES (?, LAST_INSERT_ID(1)) ON DUPLICATE KEY UPDATE value = LAST_INSERT_ID(value + 1)"#,
+        )
+        .bind(TITLE_SEQUENCE)
+        .execute(&data.db)
+        .await?
+        .last_insert_id()
+    } else {
+        0
+    };
+
+    Ok(Some(render_title_template(template, seq, id)))
+}
+
+fn normalize_title(config: &Config, title: String) -> String {
+    match config.title_case {
+        TitleCase::Raw => title,
+        TitleCase::Title => title
+            .split(' ')
+            .map(capitalize)
+            .collect::<Vec<String>>()
+            .join(" "),
+        TitleCase::Sentence => capitalize(&title),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+fn render_title_template(template: &str, seq: u64, id: &str) -> String {
+    template
+        .replace("{date}", &chrono::Utc::now().format("%Y-%m-%d").to_string())
+        .replace("{seq}", &seq.to_string())
+        .replace("{uuid}", id)
+}
+
+struct Pagination {
+    page: usize,
+    limit: usize,
+    offset: usize,
+}
+
+fn pagination(opts: &FilterOptions) -> Result<Pagination, AppError> {
+    let page = opts.page.unwrap_or(1);
+    let limit = opts.limit.unwrap_or(10);
+
+    if page == 0 {
+        return Err(AppError::BadRequest(
+            "Las paginas empiezan en 1; page=0 no es valido".to_string(),
+        ));
+    }
+
+    // Binds are i64, and the cursor list asks for one row past the page, so
+    // offset + limit + 1 has to fit in one; anything larger could never
+    // return rows anyway.
+    let offset = (page - 1)
+        .checked_mul(limit)
+        .filter(|offset| {
+            offset
+                .checked_add(limit)
+                .and_then(|end| end.checked_add(1))
+                .is_some_and(|end| i64::try_from(end).is_ok())
+        })
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "page={} con limit={} queda fuera de rango",
+                page, limit
+            ))
+        })?;
+
+    Ok(Pagination {
+        page,
+        limit,
+        offset,
+    })
+}
+
+// Column and direction for the main list. Both come from an allow-list and
+// are pasted into the SQL, so nothing from the query string reaches it as-is.
+fn list_order(opts: &FilterOptions) -> Result<(&'static str, &'static str), AppError> {
+    let column = match opts.sort_by.as_deref() {
+        None | Some("id") => "id",
+        Some("created_at") => "created_at",
+        Some("updated_at") => "updated_at",
+        Some("title") => "title",
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "sort_by no valido: {}; use created_at, updated_at o title",
+                other
+            )));
+        }
+    };
+    let direction = match opts.order.as_deref() {
+        None | Some("asc") => "ASC",
+        Some("desc") => "DESC",
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "order no valido: {}; use asc o desc",
+                other
+            )));
+        }
+    };
+
+    Ok((column, direction))
+}
+
+// Shared envelope for paged note listings: the notes, totals and RFC 8288
+// Link headers. Every non-paging key in `applied` is carried over into the
+// links so following them keeps the same filters.
+fn paginated_response(
+    config: &Config,
+    endpoint: &str,
+    path: &str,
+    pagination: &Pagination,
+    total: i64,
+    notes: Vec<NoteModelResponse>,
+    applied: serde_json::Value,
+) -> Response {
+    let total = total.max(0) as usize;
+    let total_pages = if pagination.limit == 0 {
+        0
+    } else {
+        total.div_ceil(pagination.limit)
+    };
+
+    let extra = link_params(&applied);
+    let link = |page: usize, rel: &str| {
+        format!(
+            "<{}?page={}&limit={}{}>; rel=\"{}\"",
+            path, page, pagination.limit, extra, rel
+        )
+    };
+
+    let mut links = Vec::new();
+    if total_pages > 0 {
+        links.push(link(1, "first"));
+        if pagination.page > 1 {
+            links.push(link((pagination.page - 1).min(total_pages), "prev"));
+        }
+        if pagination.page < total_pages {
+            links.push(link(pagination.page + 1, "next"));
+        }
+        links.push(link(total_pages, "last"));
+    }
+
+    let json_response = serde_json::json!({
+        "status": "ok",
+        "count": notes.len(),
+        "total": total,
+        "total_pages": total_pages,
+        "notes": notes,
+        "applied": applied
+    });
+
+    let mut response = with_cache_policy(config, endpoint, Json(json_response));
+    if !links.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+            response.headers_mut().insert(LINK, value);
+        }
+    }
+    response
+}
+
+// The main list in cursor mode: no page numbers, just the id to send as
+// `after` for the next page, or null once the last page is reached.
+fn cursor_list_response(
+    config: &Config,
+    limit: usize,
+    total: i64,
+    notes: Vec<NoteModelResponse>,
+    applied: serde_json::Value,
+    next_cursor: Option<String>,
+) -> Response {
+    let next_link = next_cursor.as_ref().and_then(|cursor| {
+        let cursor = serde_urlencoded::to_string([("after", cursor.as_str())]).ok()?;
+        Some(format!(
+            "</api/notes?{}&limit={}{}>; rel=\"next\"",
+            cursor,
+            limit,
+            link_params(&applied)
+        ))
+    });
+
+    let json_response = serde_json::json!({
+        "status": "ok",
+        "count": notes.len(),
+        "total": total.max(0),
+        "next_cursor": next_cursor,
+        "notes": notes,
+        "applied": applied
+    });
+
+    let mut response = with_cache_policy(config, "list", Json(json_response));
+    if let Some(value) = next_link.and_then(|link| HeaderValue::from_str(&link).ok()) {
+        response.headers_mut().insert(LINK, value);
+    }
+    response
+}
+
+// `&key=value` for every applied filter a followed link has to keep. Paging
+// keys are left out; each link sets its own.
+fn link_params(applied: &serde_json::Value) -> String {
+    let mut extra = String::new();
+    if let Some(applied) = applied.as_object() {
+        for (key, value) in applied {
+            if key == "page" || key == "limit" || key == "after" || value.is_null() {
+                continue;
+            }
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            if let Ok(pair) = serde_urlencoded::to_string([(key.as_str(), value.as_str())]) {
+                extra.push('&');
+                extra.push_str(&pair);
+            }
+        }
+    }
+    extra
+}
+
+// Read bodies change with the admin token and with x-features, so caches
+// have to key on both.
+fn with_cache_policy(config: &Config, endpoint: &str, response: impl IntoResponse) -> Response {
+    let mut response = response.into_response();
+    response
+        .headers_mut()
+        .insert(VARY, HeaderValue::from_static("authorization, x-features"));
+    if let Some(policy) = config.cache_policies.get(endpoint) {
+        let shared = config.admin_token.is_none();
+        if let Ok(value) = HeaderValue::from_str(&policy.header_value(shared)) {
+            response.headers_mut().insert(CACHE_CONTROL, value);
+        }
+    }
+    response
+}
+
+// Ids are always UUIDs, so anything else is rejected before it costs a query.
+fn ensure_uuid(id: &str) -> Result<(), AppError> {
+    if uuid::Uuid::parse_str(id).is_err() {
+        return Err(AppError::BadRequest(format!(
+            "El ID: {} no es un UUID valido",
+            id
+        )));
+    }
+    Ok(())
+}
+
+// With REJECT_BINARY_CONTENT the body parsed fine but is not text a note can
+// hold, hence 422 rather than a field error.
+fn ensure_text_content(config: &Config, content: Option<&str>) -> Result<(), AppError> {
+    if config.reject_binary_content && content.is_some_and(looks_binary) {
+        return Err(AppError::Unprocessable(
+            "El contenido parece binario; solo se admite texto".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// MySQL error 1062 (ER_DUP_ENTRY). SQLSTATE 23000 alone would also match
+// other integrity errors, and the message text changes with the server locale.
+fn is_duplicate_entry(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(db_err) => db_err
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .is_some_and(|mysql_err| mysql_err.number() == 1062),
+        _ => false,
+    }
+}
+
+// Errors the primary might not hit: the replica being down, out of
+// connections or refusing the query. A missing row or a decode error would
+// come back the same from the primary, so those are returned as-is.
+fn is_replica_failure(err: &sqlx::Error) -> bool {
+    matches!(
+        err,
+        sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::Protocol(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed
+            | sqlx::Error::Database(_)
+    )
+}
+
+// Rows written by other tools can leave the timestamps NULL. Each falls back
+// to the other and, failing both, to the Unix epoch rather than panicking.
+fn to_note_response(config: &Config, note: &NoteModel) -> NoteModelResponse {
+    let created_at = note.created_at.or(note.updated_at).unwrap_or_default();
+    let updated_at = note.updated_at.unwrap_or(created_at);
+    // Only the response is truncated; storage keeps whatever MySQL recorded.
+    let timestamp = |value: chrono::DateTime<chrono::Utc>| {
+        if config.truncate_timestamps {
+            value.trunc_subsecs(0)
+        } else {
+            value
+        }
+    };
+
+    NoteModelResponse {
         id: note.id.to_owned(),
         title: note.title.to_owned(),
         content: note.content.to_owned(),
-        is_published: note.is_published != 0,
-        created_at: note.created_at.unwrap(),
-        updated_at: note.updated_at.unwrap(),
+        is_published: note.is_published,
+        visibility: note.visibility.to_owned(),
+        featured: note.featured != 0,
+        featured_order: note.featured_order,
+        remind_at: note.remind_at.map(timestamp),
+        reminder_fired_at: note.reminder_fired_at.map(timestamp),
+        batch_id: note.batch_id.to_owned(),
+        archived_at: note.archived_at.map(timestamp),
+        deleted_at: note.deleted_at.map(timestamp),
+        created_at: timestamp(created_at),
+        updated_at: timestamp(updated_at),
+        age: None,
+        content_length: None,
+        preview: None,
+    }
+}
+
+fn with_age(mut response: NoteModelResponse, include_age: bool) -> NoteModelResponse {
+    if include_age {
+        response.age = Some(humanize_age(response.created_at));
+    }
+    response
+}
+
+fn humanize_age(timestamp: chrono::DateTime<chrono::Utc>) -> String {
+    let seconds = (chrono::Utc::now() - timestamp).num_seconds();
+
+    let (amount, singular, plural) = match seconds {
+        s if s < 60 => return "hace un momento".to_string(),
+        s if s < 3_600 => (s / 60, "minuto", "minutos"),
+        s if s < 86_400 => (s / 3_600, "hora", "horas"),
+        s if s < 30 * 86_400 => (s / 86_400, "dia", "dias"),
+        s if s < 365 * 86_400 => (s / (30 * 86_400), "mes", "meses"),
+        s => (s / (365 * 86_400), "año", "años"),
+    };
+
+    if amount == 1 {
+        format!("hace 1 {}", singular)
+    } else {
+        format!("hace {} {}", amount, plural)
+    }
+}
+
+pub const LOCK_HOLDER_HEADER: &str = "x-lock-holder";
+
+fn lock_holder(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(LOCK_HOLDER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|holder| !holder.is_empty())
+}
+
+fn required_lock_holder(headers: &HeaderMap) -> Result<String, AppError> {
+    match lock_holder(headers) {
+        Some(holder) => Ok(holder.to_string()),
+        None => Err(AppError::BadRequest(format!(
+            "Se requiere el encabezado {}",
+            LOCK_HOLDER_HEADER
+        ))),
+    }
+}
+
+async fn ensure_not_locked(data: &AppState, id: &str, headers: &HeaderMap) -> Result<(), AppError> {
+    let lock = sqlx::query_as!(
+        NoteLockModel,
+        r#"SELECT * FROM note_locks WHERE note_id = ? AND expires_at > CURRENT_TIMESTAMP"#,
+        id
+    )
+    .fetch_optional(&data.db)
+    .await?;
+
+    match lock {
+        Some(lock) if lock_holder(headers) != Some(lock.holder.as_str()) => {
+            Err(AppError::Locked(lock))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Notes the caller may not see answer 404 by id, rather than 403, so ids
+// cannot be probed for existence.
+fn hidden_from_caller(config: &Config, headers: &HeaderMap, note: &NoteModel) -> bool {
+    Access::of(config, headers).hides(note.is_published, &note.visibility)
+}
+
+// What the caller may see besides published public and unlisted notes. The
+// admin token is the only credential this API has. With no ADMIN_TOKEN
+// configured there is nobody to keep private notes from, so every caller sees
+// them; drafts are hidden only with HIDE_UNPUBLISHED, and then from everyone
+// without the token. Every listing, and every read or write by id, apply the
+// same rules.
+#[derive(Clone, Copy)]
+struct Access {
+    private: bool,
+    unpublished: bool,
+}
+
+impl Access {
+    fn of(config: &Config, headers: &HeaderMap) -> Access {
+        let admin = require_admin(headers, config).is_ok();
+        Access {
+            private: config.admin_token.is_none() || admin,
+            unpublished: !config.hide_unpublished || admin,
+        }
+    }
+
+    fn hides(self, is_published: bool, visibility: &str) -> bool {
+        (!self.unpublished && !is_published) || (!self.private && visibility == "private")
+    }
+}
+
+fn push_access_filter(query: &mut sqlx::QueryBuilder<'_, MySql>, access: Access) {
+    if !access.private {
+        query.push(" AND visibility <> 'private'");
+    }
+    if !access.unpublished {
+        query.push(" AND is_published = 1");
+    }
+}
+
+fn require_admin(headers: &HeaderMap, config: &Config) -> Result<(), AppError> {
+    let Some(admin_token) = &config.admin_token else {
+        return Err(AppError::Forbidden(
+            "Los endpoints de administracion estan deshabilitados".to_string(),
+        ));
+    };
+
+    let provided = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if provided != Some(admin_token.as_str()) {
+        return Err(AppError::Unauthorized(
+            "Token de administrador invalido".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn http_date(timestamp: chrono::DateTime<chrono::Utc>) -> String {
+    timestamp.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn if_modified_since(headers: &HeaderMap) -> Option<chrono::DateTime<chrono::Utc>> {
+    let value = headers.get(IF_MODIFIED_SINCE)?.to_str().ok()?;
+    chrono::DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&chrono::Utc))
+}
+
+// Weak because the body also depends on serialization details (and on
+// include_age drifting over time) that the tag does not capture byte for byte.
+fn list_etag(
+    notes: &[NoteModel],
+    last_modified: Option<chrono::DateTime<chrono::Utc>>,
+    total: i64,
+    applied: &serde_json::Value,
+) -> String {
+    let mut hasher = DefaultHasher::new();
+    for note in notes {
+        note.id.hash(&mut hasher);
+        note.updated_at.hash(&mut hasher);
+    }
+    (last_modified, total).hash(&mut hasher);
+    applied.to_string().hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(value) = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    value
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == opaque(etag))
+}
+
+fn parse_window(window: &str) -> Option<chrono::Duration> {
+    let window = window.trim();
+    if window.len() < 2 {
+        return None;
+    }
+
+    let (amount, unit) = window.split_at(window.len() - 1);
+    let amount: i64 = amount.parse().ok()?;
+    if amount <= 0 {
+        return None;
+    }
+
+    match unit {
+        "h" if amount <= 24 * 3650 => chrono::Duration::try_hours(amount),
+        "d" if amount <= 3650 => chrono::Duration::try_days(amount),
+        "w" if amount <= 520 => chrono::Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_last_modified_covers_notes_that_left_the_filter() {
+        let access = Access {
+            private: false,
+            unpublished: false,
+        };
+        let query = list_summary_query(Some("nota"), Some(true), access, &Features::default());
+        let (columns, filter) = query.sql().split_once(" FROM notes WHERE ").unwrap();
+
+        // The filter narrows the count but none of the timestamps, so a note
+        // archived, unpublished or made unlisted still moves Last-Modified.
+        assert!(!columns.contains("WHERE"));
+        for stamp in [
+            "updated_at",
+            "archived_at",
+            "reminder_fired_at",
+            "deleted_at",
+        ] {
+            assert!(columns.contains(&format!("MAX({})", stamp)), "{}", stamp);
+        }
+        for predicate in [
+            "archived_at IS NULL",
+            "visibility <> 'unlisted'",
+            "is_published = ",
+        ] {
+            assert!(filter.contains(predicate), "{}", predicate);
+        }
+    }
+
+    #[test]
+    fn missing_note_is_gone_only_when_it_was_deleted() {
+        let admin = Access {
+            private: true,
+            unpublished: true,
+        };
+        let deleted = Some((true, "public".to_string()));
+        assert!(matches!(
+            missing_note_error("a", deleted, admin),
+            AppError::NoteDeleted(_)
+        ));
+        assert!(matches!(
+            missing_note_error("a", None, admin),
+            AppError::NotFound(_)
+        ));
+    }
+
+    #[test]
+    fn missing_note_stays_404_when_it_was_hidden_from_the_caller() {
+        let anonymous = Access {
+            private: false,
+            unpublished: false,
+        };
+        for (is_published, visibility) in [(true, "private"), (false, "public")] {
+            let deleted = Some((is_published, visibility.to_string()));
+            assert!(matches!(
+                missing_note_error("a", deleted, anonymous),
+                AppError::NotFound(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_window_accepts_hours_days_and_weeks() {
+        assert_eq!(parse_window("24h"), chrono::Duration::try_hours(24));
+        assert_eq!(parse_window(" 7d "), chrono::Duration::try_days(7));
+        assert_eq!(parse_window("30d"), chrono::Duration::try_days(30));
+        assert_eq!(parse_window("2w"), chrono::Duration::try_weeks(2));
+    }
+
+    #[test]
+    fn parse_window_rejects_bad_input() {
+        for window in ["", "d", "0d", "-1d", "10m", "abcd", "3651d", "521w"] {
+            assert_eq!(parse_window(window), None, "{:?}", window);
+        }
+    }
+
+    #[test]
+    fn humanize_age_picks_the_largest_unit() {
+        let ago = |seconds| chrono::Utc::now() - chrono::Duration::seconds(seconds);
+        assert_eq!(humanize_age(ago(30)), "hace un momento");
+        assert_eq!(humanize_age(ago(65)), "hace 1 minuto");
+        assert_eq!(humanize_age(ago(3 * 3_600)), "hace 3 horas");
+        assert_eq!(humanize_age(ago(2 * 86_400)), "hace 2 dias");
+        assert_eq!(humanize_age(ago(60 * 86_400)), "hace 2 meses");
+        assert_eq!(humanize_age(ago(400 * 86_400)), "hace 1 año");
+    }
+
+    #[test]
+    fn title_template_fills_every_placeholder() {
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        assert_eq!(
+            render_title_template("Nota {seq} del {date} ({uuid})", 7, "abc"),
+            format!("Nota 7 del {} (abc)", today)
+        );
+        assert_eq!(render_title_template("{seq}-{seq}", 2, "abc"), "2-2");
+        assert_eq!(render_title_template("Sin marcas", 1, "abc"), "Sin marcas");
+    }
+
+    #[test]
+    fn if_none_match_compares_opaque_tags() {
+        let headers = |value| {
+            let mut headers = HeaderMap::new();
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_static(value));
+            headers
+        };
+        assert!(!if_none_match(&HeaderMap::new(), "\"a\""));
+        assert!(if_none_match(&headers("\"a\""), "\"a\""));
+        assert!(if_none_match(&headers("W/\"a\""), "\"a\""));
+        assert!(if_none_match(&headers("\"b\", \"a\""), "\"a\""));
+        assert!(if_none_match(&headers("*"), "\"a\""));
+        assert!(!if_none_match(&headers("\"b\""), "\"a\""));
+    }
+
+    #[test]
+    fn normalize_title_follows_title_case() {
+        let with = |title_case| Config {
+            title_case,
+            ..Config::default()
+        };
+        let title = "hOLA mundo".to_string();
+        assert_eq!(
+            normalize_title(&with(TitleCase::Raw), title.clone()),
+            "hOLA mundo"
+        );
+        assert_eq!(
+            normalize_title(&with(TitleCase::Title), title.clone()),
+            "Hola Mundo"
+        );
+        assert_eq!(
+            normalize_title(&with(TitleCase::Sentence), title),
+            "Hola mundo"
+        );
+    }
+
+    #[test]
+    fn link_params_keep_filters_but_not_paging() {
+        let applied = serde_json::json!({
+            "page": 2,
+            "limit": 10,
+            "after": "abc",
+            "search": "dos palabras",
+            "is_published": true,
+            "sort_by": null
+        });
+        assert_eq!(
+            link_params(&applied),
+            "&is_published=true&search=dos+palabras"
+        );
+    }
+
+    #[test]
+    fn paginated_response_links_neighbouring_pages() {
+        let page = |page| {
+            let pagination = Pagination {
+                page,
+                limit: 10,
+                offset: (page - 1) * 10,
+            };
+            let applied = serde_json::json!({ "search": "a" });
+            let response = paginated_response(
+                &Config::default(),
+                "list",
+                "/api/notes",
+                &pagination,
+                25,
+                Vec::new(),
+                applied,
+            );
+            response
+                .headers()
+                .get(LINK)
+                .map(|link| link.to_str().unwrap().to_string())
+        };
+
+        assert_eq!(
+            page(2).unwrap(),
+            "</api/notes?page=1&limit=10&search=a>; rel=\"first\", \
+             </api/notes?page=1&limit=10&search=a>; rel=\"prev\", \
+             </api/notes?page=3&limit=10&search=a>; rel=\"next\", \
+             </api/notes?page=3&limit=10&search=a>; rel=\"last\""
+        );
+        let first = page(1).unwrap();
+        assert!(!first.contains("rel=\"prev\""));
+        assert!(first.contains("page=2&limit=10&search=a>; rel=\"next\""));
+        let last = page(3).unwrap();
+        assert!(!last.contains("rel=\"next\""));
+    }
+
+    #[test]
+    fn paginated_response_has_no_links_without_results() {
+        let pagination = Pagination {
+            page: 1,
+            limit: 10,
+            offset: 0,
+        };
+        let response = paginated_response(
+            &Config::default(),
+            "list",
+            "/api/notes",
+            &pagination,
+            0,
+            Vec::new(),
+            serde_json::json!({}),
+        );
+        assert!(response.headers().get(LINK).is_none());
+    }
+
+    #[test]
+    fn poll_cursor_round_trips() {
+        let updated_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let cursor = poll_cursor(updated_at, "abc_def");
+        assert_eq!(
+            parse_poll_cursor(&cursor),
+            Some((updated_at, "abc_def".to_string()))
+        );
+        assert!(parse_poll_cursor("abc").is_none());
+        assert!(parse_poll_cursor("x_abc").is_none());
+    }
+
+    #[test]
+    fn poll_timeout_is_capped() {
+        let secs = std::time::Duration::from_secs;
+        assert_eq!(parse_poll_timeout("30", 60), Some(secs(30)));
+        assert_eq!(parse_poll_timeout(" 30s ", 60), Some(secs(30)));
+        assert_eq!(parse_poll_timeout("60s", 60), Some(secs(60)));
+        assert_eq!(parse_poll_timeout("61", 60), None);
+        assert_eq!(parse_poll_timeout("-1", 60), None);
+    }
+
+    #[test]
+    fn pagination_defaults_and_offsets() {
+        let first = pagination(&FilterOptions::default()).unwrap();
+        assert_eq!((first.page, first.limit, first.offset), (1, 10, 0));
+
+        let opts = FilterOptions {
+            page: Some(3),
+            limit: Some(25),
+            ..FilterOptions::default()
+        };
+        assert_eq!(pagination(&opts).unwrap().offset, 50);
+    }
+
+    #[test]
+    fn pagination_rejects_page_zero_and_overflow() {
+        let with = |page, limit| FilterOptions {
+            page: Some(page),
+            limit: Some(limit),
+            ..FilterOptions::default()
+        };
+        for (page, limit) in [
+            (0, 10),
+            (usize::MAX, 10),
+            (2, usize::MAX),
+            (1, i64::MAX as usize),
+        ] {
+            assert!(
+                matches!(pagination(&with(page, limit)), Err(AppError::BadRequest(_))),
+                "page={} limit={}",
+                page,
+                limit
+            );
+        }
+        assert!(pagination(&with(1, i64::MAX as usize - 1)).is_ok());
+    }
+
+    #[test]
+    fn list_order_maps_known_columns() {
+        let with = |sort_by: Option<&str>, order: Option<&str>| FilterOptions {
+            sort_by: sort_by.map(str::to_string),
+            order: order.map(str::to_string),
+            ..FilterOptions::default()
+        };
+        assert_eq!(list_order(&with(None, None)).unwrap(), ("id", "ASC"));
+        assert_eq!(
+            list_order(&with(Some("updated_at"), Some("desc"))).unwrap(),
+            ("updated_at", "DESC")
+        );
+        assert_eq!(
+            list_order(&with(Some("title"), Some("asc"))).unwrap(),
+            ("title", "ASC")
+        );
+    }
+
+    #[test]
+    fn list_order_rejects_anything_else() {
+        let with = |sort_by: &str, order: &str| FilterOptions {
+            sort_by: Some(sort_by.to_string()),
+            order: Some(order.to_string()),
+            ..FilterOptions::default()
+        };
+        for (sort_by, order) in [("content; DROP", "asc"), ("id", "random"), ("TITLE", "asc")] {
+            assert!(matches!(
+                list_order(&with(sort_by, order)),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn field_aliases_rename_old_keys() {
+        let config = Config {
+            field_aliases: [("name", "title"), ("text", "content")]
+                .into_iter()
+                .map(|(alias, field)| (alias.to_string(), field.to_string()))
+                .collect(),
+            ..Config::default()
+        };
+
+        let body = with_field_aliases(
+            &config,
+            serde_json::json!({ "name": "viejo", "text": "cuerpo" }),
+        );
+        assert_eq!(
+            body,
+            serde_json::json!({ "title": "viejo", "content": "cuerpo" })
+        );
+
+        // The real field wins, and the alias is still dropped.
+        let body = with_field_aliases(
+            &config,
+            serde_json::json!({ "name": "viejo", "title": "nuevo" }),
+        );
+        assert_eq!(body, serde_json::json!({ "title": "nuevo" }));
+
+        let body = with_field_aliases(&config, serde_json::json!(["name"]));
+        assert_eq!(body, serde_json::json!(["name"]));
+    }
+
+    #[test]
+    fn binary_content_is_rejected_only_when_configured() {
+        let rejecting = Config {
+            reject_binary_content: true,
+            ..Config::default()
+        };
+        assert!(matches!(
+            ensure_text_content(&rejecting, Some("a\0b")),
+            Err(AppError::Unprocessable(_))
+        ));
+        assert!(ensure_text_content(&rejecting, Some("texto")).is_ok());
+        assert!(ensure_text_content(&rejecting, None).is_ok());
+        assert!(ensure_text_content(&Config::default(), Some("a\0b")).is_ok());
     }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use axum::{response::IntoResponse, routing::get, Json, Router};
 
@@ -17,8 +17,15 @@ async fn main() {
     println!("SERVIDOR CRUD");
 
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must set");
+    let max_connections = env_nonzero("DATABASE_MAX_CONNECTIONS", 10);
+    let acquire_timeout_secs = env_nonzero("DATABASE_ACQUIRE_TIMEOUT_SECS", 30);
+    println!(
+        "pool: {} conexiones, espera maxima {}s",
+        max_connections, acquire_timeout_secs
+    );
     let pool = match MySqlPoolOptions::new()
-        .max_connections(10)
+        .max_connections(max_connections as u32)
+        .acquire_timeout(Duration::from_secs(acquire_timeout_secs))
         .connect(&database_url)
         .await
     {
@@ -44,6 +51,20 @@ async fn main() {
         .unwrap();
 }
 
+fn env_nonzero(key: &str, default: u64) -> u64 {
+    let value = match std::env::var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a valid value, got {:?}", key, value)),
+        Err(_) => default,
+    };
+    if value == 0 {
+        panic!("{} must be greater than zero", key);
+    }
+    value
+}
+
 pub async fn health_check_handler() -> impl IntoResponse {
     const MESSAGE: &str = "API";
 
@@ -1,56 +1,299 @@
+mod archive;
+mod config;
+mod error;
+mod extract;
+mod feature;
 mod handler;
 mod model;
+mod preview;
+mod reminder;
 mod route;
 mod schema;
+mod webhook;
 
-use std::sync::Arc;
+use std::{
+    future::IntoFuture,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use axum::http::{header::CONTENT_TYPE, Method};
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{
+            AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+            LINK, LOCATION,
+        },
+        HeaderName,
+    },
+    middleware::{self, Next},
+    response::Response,
+    ServiceExt,
+};
 
 use dotenv::dotenv;
-use tokio::net::TcpListener;
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, mpsc, watch, Semaphore},
+};
 
 use sqlx::mysql::{MySqlPool, MySqlPoolOptions};
 
-use route::create_router;
-use tower_http::cors::{Any, CorsLayer};
+use config::Config;
+use feature::FEATURES_HEADER;
+use handler::LOCK_HOLDER_HEADER;
+use model::NoteEvent;
+use route::{create_router, normalize_trailing_slash, ROUTE_METHODS};
+use tower::Layer;
+use tower_http::{
+    cors::{Any, CorsLayer},
+    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
 
 pub struct AppState {
     db: MySqlPool,
+    read_db: MySqlPool,
+    config: Config,
+    events: broadcast::Sender<NoteEvent>,
+    webhook_queue: mpsc::UnboundedSender<NoteEvent>,
+    poll_slots: Semaphore,
+    in_flight: AtomicUsize,
+}
+
+impl AppState {
+    // Long-polls only need to hear that something changed, so the bounded
+    // broadcast may drop events they lag behind on. Webhooks promise one POST
+    // per event, so they get their own queue that never drops one; a batch
+    // of 100 notes would overflow the broadcast at once.
+    pub fn publish(&self, event: NoteEvent) {
+        let _ = self.webhook_queue.send(event.clone());
+        let _ = self.events.send(event);
+    }
 }
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    println!("SERVIDOR CRUD");
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+    tracing::info!("SERVIDOR CRUD");
+
+    let config = Config::init();
 
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must set");
-    let pool = match MySqlPoolOptions::new()
-        .max_connections(10)
-        .connect(&database_url)
-        .await
-    {
-        Ok(pool) => {
-            println!(" Conectado a la base de datos!");
-            pool
+    let pool = connect(&database_url, config.write_pool_max_connections, &config).await;
+    // Reads go to the replica when one is configured.
+    let read_database_url = std::env::var("READ_DATABASE_URL").unwrap_or(database_url.clone());
+    let read_pool = connect(&read_database_url, config.read_pool_max_connections, &config).await;
+    tracing::info!("Conectado a la base de datos!");
+    tracing::info!(
+        "pools: escritura {} conexiones, lectura {} conexiones, espera maxima {}s",
+        config.write_pool_max_connections,
+        config.read_pool_max_connections,
+        config.database_acquire_timeout_secs
+    );
+
+    // Browsers only hand scripts the response headers listed in
+    // expose_headers, and the conditional headers have to be allowed to be
+    // sent at all.
+    let cors = CorsLayer::new()
+        .allow_methods(ROUTE_METHODS)
+        .allow_origin(Any)
+        .allow_headers([
+            CONTENT_TYPE,
+            AUTHORIZATION,
+            IF_NONE_MATCH,
+            IF_MODIFIED_SINCE,
+            HeaderName::from_static(FEATURES_HEADER),
+            HeaderName::from_static(LOCK_HOLDER_HEADER),
+        ])
+        .expose_headers([LOCATION, LINK, ETAG, LAST_MODIFIED]);
+
+    let poll_slots = Semaphore::new(config.poll_max_subscribers);
+    let (webhook_queue, webhook_events) = mpsc::unbounded_channel();
+    let app_state = Arc::new(AppState {
+        db: pool.clone(),
+        read_db: read_pool.clone(),
+        config,
+        events: broadcast::channel(16).0,
+        webhook_queue,
+        poll_slots,
+        in_flight: AtomicUsize::new(0),
+    });
+    tokio::spawn(reminder::run(app_state.clone()));
+    tokio::spawn(archive::run(app_state.clone()));
+    tokio::spawn(webhook::run(app_state.clone(), webhook_events));
+
+    let address = format!("{}:{}", app_state.config.host, app_state.config.port);
+    // One span per request with method and path; the response event adds the
+    // status and latency.
+    let trace = TraceLayer::new_for_http()
+        .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+        .on_response(DefaultOnResponse::new().level(Level::INFO));
+    let router = create_router(app_state.clone())
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            track_in_flight,
+        ))
+        .layer(cors)
+        .layer(trace);
+    let app =
+        middleware::from_fn_with_state(app_state.clone(), normalize_trailing_slash).layer(router);
+
+    tracing::info!("escuchando en {}", address);
+
+    let listener = TcpListener::bind(&address).await.unwrap();
+    let (shutdown_started, shutdown) = watch::channel(false);
+    let server = axum::serve(listener, ServiceExt::<Request>::into_make_service(app))
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            let _ = shutdown_started.send(true);
+        });
+    let drain_timeout = Duration::from_secs(app_state.config.shutdown_drain_timeout_secs);
+
+    tokio::select! {
+        result = server.into_future() => result.unwrap(),
+        _ = drain_deadline(shutdown, drain_timeout) => {
+            // Returning drops the connection tasks with the runtime. The
+            // pools are not closed: that would wait for those same requests.
+            tracing::warn!(
+                "{} peticiones seguian activas tras {:?}; se cierran",
+                app_state.in_flight.load(Ordering::Relaxed),
+                drain_timeout
+            );
+            return;
         }
-        Err(err) => {
-            println!("conexion fallida con la base de datos: {:?}", err);
-            std::process::exit(1);
+    }
+
+    pool.close().await;
+    read_pool.close().await;
+}
+
+// Counts requests still being served, for the log line at the drain cutoff.
+async fn track_in_flight(State(data): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    struct InFlight<'a>(&'a AtomicUsize);
+    impl Drop for InFlight<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
         }
+    }
+
+    data.in_flight.fetch_add(1, Ordering::Relaxed);
+    // A guard, so a request dropped halfway (the client went away) still
+    // leaves the count.
+    let _guard = InFlight(&data.in_flight);
+    next.run(req).await
+}
+
+// Resolves `timeout` after shutdown starts, and never before it does.
+async fn drain_deadline(mut shutdown: watch::Receiver<bool>, timeout: Duration) {
+    if shutdown.wait_for(|started| *started).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+    tokio::time::sleep(timeout).await;
+}
+
+// Resolves on Ctrl-C or SIGTERM; axum then stops accepting connections and
+// lets in-flight requests finish before serve returns.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl-C handler");
     };
 
-    let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST])
-        .allow_origin(Any)
-        .allow_headers([CONTENT_TYPE]);
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("shutting down");
+}
+
+// Reads and writes get separate pools so a burst of list traffic cannot take
+// every connection a write needs.
+// MySQL is often still starting when docker-compose brings the app up, so a
+// failed connection is retried with a doubling delay before giving up.
+async fn connect(database_url: &str, max_connections: u32, config: &Config) -> MySqlPool {
+    let max_attempts = config.database_connect_attempts;
+    let mut attempt = 1;
+    loop {
+        let result = MySqlPoolOptions::new()
+            .max_connections(max_connections)
+            .acquire_timeout(Duration::from_secs(config.database_acquire_timeout_secs))
+            .connect(database_url)
+            .await;
+
+        match result {
+            Ok(pool) => return pool,
+            Err(err) if attempt < max_attempts => {
+                let delay = Duration::from_millis(
+                    config.database_connect_retry_ms << (attempt - 1).min(6),
+                );
+                tracing::warn!(
+                    "conexion fallida con la base de datos (intento {}/{}), reintentando en {:?}: {:?}",
+                    attempt,
+                    max_attempts,
+                    delay,
+                    err
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => {
+                tracing::error!(
+                    "conexion fallida con la base de datos (intento {}/{}): {:?}",
+                    attempt,
+                    max_attempts,
+                    err
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+}
 
-    let app = create_router(Arc::new(AppState { db: pool.clone() })).layer(cors);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!(" 0.0.0.0:8080");
+    #[tokio::test]
+    async fn drain_deadline_waits_for_shutdown() {
+        let (_started, shutdown) = watch::channel(false);
+        let deadline = drain_deadline(shutdown, Duration::ZERO);
+        assert!(tokio::time::timeout(Duration::from_millis(50), deadline)
+            .await
+            .is_err());
+    }
 
-    let listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    #[tokio::test]
+    async fn drain_deadline_fires_after_the_timeout() {
+        let (started, shutdown) = watch::channel(false);
+        let timeout = Duration::from_millis(30);
+        let begin = tokio::time::Instant::now();
+        started.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), drain_deadline(shutdown, timeout))
+            .await
+            .unwrap();
+        assert!(begin.elapsed() >= timeout);
+    }
 }
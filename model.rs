@@ -7,19 +7,98 @@ pub struct NoteModel {
     pub id: String,
     pub title: String,
     pub content: String,
-    pub is_published: i8,
+    // The query_as! macros map TINYINT(1) to i8, so macro queries select
+    // `is_published AS "is_published: bool"` instead of `*`.
+    pub is_published: bool,
+    pub visibility: String,
+    pub featured: i8,
+    pub featured_order: Option<i32>,
+    pub remind_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub reminder_fired_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub batch_id: Option<String>,
+    pub archived_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[allow(non_snake_case)]
 pub struct NoteModelResponse {
     pub id: String,
     pub title: String,
     pub content: String,
     pub is_published: bool,
+    pub visibility: String,
+    pub featured: bool,
+    pub featured_order: Option<i32>,
+    pub remind_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub reminder_fired_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    pub archived_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_length: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+
+// A main list row; content_length is only selected when the client asks.
+#[derive(Debug, sqlx::FromRow)]
+pub struct NoteListRow {
+    #[sqlx(flatten)]
+    pub note: NoteModel,
+    #[sqlx(default)]
+    pub content_length: Option<i64>,
+}
+
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow)]
+pub struct NoteLockModel {
+    pub note_id: String,
+    pub holder: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+
+// Sent on AppState::events for long-poll waiters and webhooks.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NoteEvent {
+    Created {
+        note: NoteModelResponse,
+    },
+    Updated {
+        note: NoteModelResponse,
+    },
+    Deleted {
+        id: String,
+    },
+    Restored {
+        note: NoteModelResponse,
+    },
+    Imported {
+        ids: Vec<String>,
+    },
+    BulkUpdated {
+        affected: u64,
+    },
+    Archived {
+        ids: Vec<String>,
+    },
+    ReminderDue {
+        id: String,
+        title: String,
+        remind_at: chrono::DateTime<chrono::Utc>,
+    },
 }
@@ -0,0 +1,180 @@
+// Plain-text card previews for the note list. This is not a markdown parser:
+// it drops the syntax clients would otherwise have to strip themselves
+// (headings, quotes, list markers, emphasis, code fences, links, images and
+// inline HTML) and keeps the words.
+pub fn markdown_preview(content: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    for line in content.lines() {
+        let line = strip_block_syntax(line.trim());
+        if line.is_empty() {
+            continue;
+        }
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&strip_inline_syntax(line));
+    }
+
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    truncate_at_word(&text, max_chars)
+}
+
+fn strip_block_syntax(line: &str) -> &str {
+    if line.starts_with("```") || line.starts_with("~~~") || is_rule(line) {
+        return "";
+    }
+
+    let mut line = line;
+    loop {
+        let heading = line.trim_start_matches('#');
+        let is_heading =
+            heading.len() < line.len() && (heading.is_empty() || heading.starts_with(' '));
+        let stripped = if is_heading { heading } else { line };
+        let stripped = stripped.trim_start_matches('>').trim_start();
+        let stripped = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| stripped.strip_prefix(marker))
+            .or_else(|| strip_ordered_marker(stripped))
+            .unwrap_or(stripped);
+        let stripped = ["[ ] ", "[x] ", "[X] "]
+            .iter()
+            .find_map(|marker| stripped.strip_prefix(marker))
+            .unwrap_or(stripped)
+            .trim_start();
+        if stripped == line {
+            return line;
+        }
+        line = stripped;
+    }
+}
+
+fn strip_ordered_marker(line: &str) -> Option<&str> {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    line[digits..]
+        .strip_prefix(". ")
+        .or_else(|| line[digits..].strip_prefix(") "))
+}
+
+fn is_rule(line: &str) -> bool {
+    let compact = line.replace(' ', "");
+    compact.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|marker| compact.chars().all(|c| c == *marker))
+}
+
+fn strip_inline_syntax(line: &str) -> String {
+    let chars = line.chars().collect::<Vec<char>>();
+    let mut text = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            // ![alt](url) and [text](url) keep only the alt text or link text.
+            '!' if chars.get(i + 1) == Some(&'[') => {}
+            '[' => {
+                if let Some((label, end)) = link_label(&chars, i) {
+                    text.push_str(&strip_inline_syntax(&label));
+                    i = end;
+                    continue;
+                }
+                text.push('[');
+            }
+            '<' if chars
+                .get(i + 1)
+                .is_some_and(|c| c.is_ascii_alphabetic() || *c == '/') =>
+            {
+                match chars[i..].iter().position(|c| *c == '>') {
+                    Some(len) => i += len,
+                    None => text.push('<'),
+                }
+            }
+            '*' | '`' | '~' => {}
+            // snake_case and similar keep their underscores.
+            '_' => {
+                let inside_word = i > 0
+                    && chars[i - 1].is_alphanumeric()
+                    && chars.get(i + 1).is_some_and(|c| c.is_alphanumeric());
+                if inside_word {
+                    text.push('_');
+                }
+            }
+            c => text.push(c),
+        }
+        i += 1;
+    }
+    text
+}
+
+// For `[label](target)` starting at `start`, the label and the index just
+// past the closing parenthesis.
+fn link_label(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let close = start + chars[start..].iter().position(|c| *c == ']')?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let end = close + 1 + chars[close + 1..].iter().position(|c| *c == ')')?;
+    Some((chars[start + 1..close].iter().collect(), end + 1))
+}
+
+fn truncate_at_word(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let cut = text
+        .char_indices()
+        .nth(max_chars)
+        .map(|(index, _)| index)
+        .unwrap_or(text.len());
+    // Back up to the last space unless the cut already falls between words.
+    let head = &text[..cut];
+    let head = if text[cut..].starts_with(' ') {
+        head
+    } else {
+        head.rfind(' ').map(|space| &head[..space]).unwrap_or(head)
+    };
+    format!("{}…", head.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preview_strips_block_and_inline_syntax() {
+        let content =
+            "# Titulo\n\n- **negrita** y _cursiva_\n> cita con [enlace](http://x) ![foto](y.png)";
+        assert_eq!(
+            markdown_preview(content, 100),
+            "Titulo negrita y cursiva cita con enlace foto"
+        );
+    }
+
+    #[test]
+    fn preview_drops_fences_and_rules_but_keeps_snake_case() {
+        let content = "```rust\nlet note_id = 1;\n```\n---\n1. paso `uno`\n- [x] hecho";
+        assert_eq!(
+            markdown_preview(content, 100),
+            "let note_id = 1; paso uno hecho"
+        );
+    }
+
+    #[test]
+    fn truncate_keeps_short_text() {
+        assert_eq!(truncate_at_word("hola mundo", 10), "hola mundo");
+    }
+
+    #[test]
+    fn truncate_backs_up_to_the_last_word() {
+        assert_eq!(truncate_at_word("hola mundo cruel", 12), "hola mundo…");
+        assert_eq!(truncate_at_word("hola mundo cruel", 10), "hola mundo…");
+    }
+
+    #[test]
+    fn truncate_counts_characters_not_bytes() {
+        assert_eq!(truncate_at_word("año año", 5), "año…");
+    }
+}
@@ -0,0 +1,60 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    model::{NoteEvent, NoteModel},
+    AppState,
+};
+
+const REMINDER_BATCH_SIZE: i64 = 100;
+
+pub async fn run(data: Arc<AppState>) {
+    if data.config.reminder_interval_secs == 0 {
+        return;
+    }
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(data.config.reminder_interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(err) = fire_due_reminders(&data).await {
+            tracing::error!("error enviando recordatorios: {:?}", err);
+        }
+    }
+}
+
+// Each reminder is claimed with a conditional UPDATE before its event is sent,
+// so it fires once even when several instances share the database.
+async fn fire_due_reminders(data: &AppState) -> Result<(), sqlx::Error> {
+    let due = sqlx::query_as!(
+        NoteModel,
+        r#"SELECT id, title, content, is_published AS "is_published: bool", visibility, featured, featured_order, remind_at, reminder_fired_at, batch_id, archived_at, deleted_at, created_at, updated_at FROM notes WHERE remind_at <= CURRENT_TIMESTAMP AND reminder_fired_at IS NULL AND deleted_at IS NULL ORDER BY remind_at, id LIMIT ?"#,
+        REMINDER_BATCH_SIZE
+    )
+    .fetch_all(&data.db)
+    .await?;
+
+    for note in due {
+        let Some(remind_at) = note.remind_at else {
+            continue;
+        };
+
+        // Keeps updated_at as is: firing a reminder is not an edit.
+        let claimed = sqlx::query!(
+            r#"UPDATE notes SET reminder_fired_at = CURRENT_TIMESTAMP, updated_at = updated_at WHERE id = ? AND remind_at = ? AND reminder_fired_at IS NULL AND deleted_at IS NULL"#,
+            &note.id,
+            remind_at
+        )
+        .execute(&data.db)
+        .await?;
+
+        if claimed.rows_affected() == 1 {
+            data.publish(NoteEvent::ReminderDue {
+                id: note.id,
+                title: note.title,
+                remind_at,
+            });
+        }
+    }
+
+    Ok(())
+}
@@ -1,29 +1,152 @@
 use std::sync::Arc;
 
 use axum::{
-    routing::{get, post},
+    extract::{Request, State},
+    http::{header::LOCATION, Method, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, patch, post, put},
     Router,
 };
 
 use crate::{
+    config::TrailingSlash,
+    feature::parse_features,
     handler::{
-        create_note_handler, delete_note_handler, edit_note_handler, get_note_handler,
-        health_check_handler, note_list_handler,
+        autosave_note_handler, batch_create_notes_handler, bulk_delete_notes_handler,
+        bulk_set_notes_handler, create_note_handler, delete_note_handler, draft_note_list_handler,
+        edit_note_handler, explain_note_filter_handler, export_note_markdown_handler,
+        feature_note_handler, featured_note_list_handler, get_note_handler, health_check_handler,
+        import_notes_handler, incomplete_note_list_handler, lock_note_handler, merge_notes_handler,
+        note_batch_handler, note_count_handler, note_delta_handler, note_ids_handler,
+        note_list_handler, note_stats_handler, poll_notes_handler, publish_note_handler,
+        restore_note_handler, schema_info_handler, unlock_note_handler, unpublish_note_handler,
+        upcoming_reminders_handler, validate_note_handler,
     },
     AppState,
 };
 
+// Every method registered below; main.rs feeds this to the CORS layer, so a
+// route using a new method must add it here too.
+pub const ROUTE_METHODS: [Method; 5] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+];
+
 pub fn create_router(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/api/healthcheck", get(health_check_handler))
         .route("/api/notes", post(create_note_handler))
         .route("/api/notes", get(note_list_handler))
+        .route("/api/notes/ids", get(note_ids_handler))
+        .route("/api/notes/count", get(note_count_handler))
+        .route("/api/notes/stats", get(note_stats_handler))
+        .route("/api/notes/delta", get(note_delta_handler))
+        .route("/api/notes/poll", get(poll_notes_handler))
+        .route("/api/notes/incomplete", get(incomplete_note_list_handler))
+        .route(
+            "/api/notes/reminders/upcoming",
+            get(upcoming_reminders_handler),
+        )
+        .route("/api/notes/drafts", get(draft_note_list_handler))
+        .route("/api/notes/featured", get(featured_note_list_handler))
+        .route("/api/notes/batch", post(batch_create_notes_handler))
+        .route("/api/notes/batch/:batch_id", get(note_batch_handler))
+        .route("/api/notes/bulk-set", post(bulk_set_notes_handler))
+        .route("/api/notes/bulk-delete", post(bulk_delete_notes_handler))
+        .route("/api/notes/merge", post(merge_notes_handler))
+        .route("/api/notes/import", post(import_notes_handler))
+        .route("/api/notes/validate", post(validate_note_handler))
+        .route("/api/notes/explain", post(explain_note_filter_handler))
         .route(
             "/api/notes/:id",
             get(get_note_handler)
                 .patch(edit_note_handler)
                 .delete(delete_note_handler),
         )
+        .route(
+            "/api/notes/:id/export.md",
+            get(export_note_markdown_handler),
+        )
+        .route("/api/notes/:id/publish", patch(publish_note_handler))
+        .route("/api/notes/:id/unpublish", patch(unpublish_note_handler))
+        .route("/api/notes/:id/restore", post(restore_note_handler))
+        .route("/api/notes/:id/autosave", post(autosave_note_handler))
+        .route("/api/notes/:id/lock", post(lock_note_handler))
+        .route("/api/notes/:id/unlock", post(unlock_note_handler))
+        .route("/api/admin/schema", get(schema_info_handler))
+        .route("/api/admin/notes/:id/featured", put(feature_note_handler))
+        .layer(middleware::from_fn(parse_features))
         .with_state(app_state)
 }
 
+// Runs before routing, so it has to wrap the whole router rather than be
+// added with `Router::layer`.
+pub async fn normalize_trailing_slash(
+    State(data): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let Some(path_and_query) = without_trailing_slash(req.uri()) else {
+        return next.run(req).await;
+    };
+
+    match data.config.trailing_slash {
+        TrailingSlash::Strict => next.run(req).await,
+        TrailingSlash::Redirect => {
+            (StatusCode::PERMANENT_REDIRECT, [(LOCATION, path_and_query)]).into_response()
+        }
+        TrailingSlash::Rewrite => {
+            let mut parts = req.uri().clone().into_parts();
+            parts.path_and_query = path_and_query.parse().ok();
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *req.uri_mut() = uri;
+            }
+            next.run(req).await
+        }
+    }
+}
+
+// The path and query to use instead of `uri`, or None when its path has no
+// trailing slash to drop.
+fn without_trailing_slash(uri: &Uri) -> Option<String> {
+    let path = uri.path();
+    if path.len() <= 1 || !path.ends_with('/') {
+        return None;
+    }
+
+    let trimmed = path.trim_end_matches('/');
+    let trimmed = if trimmed.is_empty() { "/" } else { trimmed };
+    Some(match uri.query() {
+        Some(query) => format!("{}?{}", trimmed, query),
+        None => trimmed.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_slash_is_dropped_and_the_query_kept() {
+        let target = |uri: &str| without_trailing_slash(&uri.parse().unwrap());
+        assert_eq!(target("/api/notes/"), Some("/api/notes".to_string()));
+        assert_eq!(target("/api/notes//"), Some("/api/notes".to_string()));
+        assert_eq!(
+            target("/api/notes/?page=2"),
+            Some("/api/notes?page=2".to_string())
+        );
+        assert_eq!(target("//"), Some("/".to_string()));
+    }
+
+    #[test]
+    fn paths_without_a_trailing_slash_are_left_alone() {
+        let target = |uri: &str| without_trailing_slash(&uri.parse().unwrap());
+        assert_eq!(target("/"), None);
+        assert_eq!(target("/api/notes"), None);
+        assert_eq!(target("/api/notes?page=2"), None);
+    }
+}
@@ -1,25 +1,344 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 
 #[derive(Deserialize, Debug, Default)]
 pub struct FilterOptions {
     pub page: Option<usize>,
     pub limit: Option<usize>,
+    pub include_age: Option<bool>,
+    pub search: Option<String>,
+    pub is_published: Option<bool>,
+    pub sort_by: Option<String>,
+    pub order: Option<String>,
+    pub with_content_length: Option<bool>,
+    pub with_preview: Option<bool>,
+    // Cursor mode: the last id of the previous page, or empty for the first.
+    // Excludes `page`.
+    pub after: Option<String>,
+}
+
+
+#[derive(Deserialize, Debug, Default)]
+pub struct NoteOptions {
+    pub include_age: Option<bool>,
+}
+
+
+#[derive(Deserialize, Debug, Default)]
+pub struct EditNoteOptions {
+    pub upsert: Option<bool>,
+}
+
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ImportOptions {
+    pub format: Option<String>,
+    pub dedupe: Option<bool>,
+}
+
+
+#[derive(Deserialize, Debug, Default)]
+pub struct StatsOptions {
+    pub window: Option<String>,
+}
+
+
+#[derive(Deserialize, Debug, Default)]
+pub struct IdsOptions {
+    pub with_updated_at: Option<bool>,
+    pub search: Option<String>,
+    pub is_published: Option<bool>,
+}
+
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CountOptions {
+    pub approximate: Option<bool>,
+}
+
+
+#[derive(Deserialize, Debug, Default)]
+pub struct PollOptions {
+    pub since: Option<String>,
+    pub timeout: Option<String>,
+}
+
+
+#[derive(Deserialize, Debug)]
+pub struct DeltaOptions {
+    pub from: chrono::DateTime<chrono::Utc>,
+    pub to: chrono::DateTime<chrono::Utc>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Private,
+    Unlisted,
+    Public,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Private => "private",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Public => "public",
+        }
+    }
 }
 
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreateNoteSchema {
-    pub title: String,
-    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_published: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+pub const MAX_TITLE_LENGTH: usize = 255;
+pub const MAX_CONTENT_BYTES: usize = 65_535;
+
+
+#[derive(Serialize, Debug)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl CreateNoteSchema {
+    // Runs before defaults are filled in, so a whitespace-only title counts
+    // as empty rather than as missing.
+    pub fn trim(&mut self) {
+        self.title = self.title.take().map(|title| title.trim().to_string());
+        self.content = self
+            .content
+            .take()
+            .map(|content| content.trim().to_string());
+    }
+
+    pub fn validate(&self, max_content_bytes: usize) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        match &self.title {
+            None => errors.push(FieldError {
+                field: "title",
+                message: "El titulo es obligatorio".to_string(),
+            }),
+            Some(title) => validate_title(title, &mut errors),
+        }
+
+        match &self.content {
+            None => errors.push(FieldError {
+                field: "content",
+                message: "El contenido es obligatorio".to_string(),
+            }),
+            Some(content) => validate_content(content, max_content_bytes, &mut errors),
+        }
+
+        errors
+    }
+}
+
+fn validate_title(title: &str, errors: &mut Vec<FieldError>) {
+    if title.trim().is_empty() {
+        errors.push(FieldError {
+            field: "title",
+            message: "El titulo no puede estar vacio".to_string(),
+        });
+    } else if title.chars().count() > MAX_TITLE_LENGTH {
+        errors.push(FieldError {
+            field: "title",
+            message: format!("El titulo no puede superar {} caracteres", MAX_TITLE_LENGTH),
+        });
+    }
+}
+
+// JSON strings are always valid UTF-8, so binary sent as text shows up as NUL
+// bytes, other control characters or the U+FFFD left by a lossy decode.
+const MAX_BINARY_CHAR_RATIO: f64 = 0.1;
+
+pub fn looks_binary(content: &str) -> bool {
+    let mut total = 0;
+    let mut suspicious = 0;
+    for c in content.chars() {
+        if c == '\0' {
+            return true;
+        }
+        total += 1;
+        if (c.is_control() && !matches!(c, '\n' | '\r' | '\t')) || c == '\u{FFFD}' {
+            suspicious += 1;
+        }
+    }
+    total > 0 && suspicious as f64 / total as f64 > MAX_BINARY_CHAR_RATIO
+}
+
+pub fn validate_content(content: &str, max_content_bytes: usize, errors: &mut Vec<FieldError>) {
+    if content.len() > max_content_bytes {
+        errors.push(FieldError {
+            field: "content",
+            message: format!("El contenido no puede superar {} bytes", max_content_bytes),
+        });
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AutosaveNoteSchema {
+    pub content: String,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl AutosaveNoteSchema {
+    pub fn trim(&mut self) {
+        self.content = self.content.trim().to_string();
+    }
+
+    pub fn validate(&self, max_content_bytes: usize) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        validate_content(&self.content, max_content_bytes, &mut errors);
+        errors
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FeatureNoteSchema {
+    pub featured: bool,
+    pub featured_order: Option<i32>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkDeleteSchema {
+    pub ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MergeNotesSchema {
+    pub primary: String,
+    pub others: Vec<String>,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BulkSetFilter {
+    pub is_published: Option<bool>,
+    pub visibility: Option<Visibility>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BulkSetChanges {
+    pub is_published: Option<bool>,
+    pub visibility: Option<Visibility>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkSetSchema {
+    #[serde(default)]
+    pub filter: BulkSetFilter,
+    pub set: BulkSetChanges,
+    #[serde(default)]
+    pub confirm_all: bool,
 }
 
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UpdateNoteSchema {
     pub title: Option<String>,
-    pub content: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub content: Option<Option<String>>,
     pub is_published: Option<bool>,
+    pub visibility: Option<Visibility>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub remind_at: Option<Option<chrono::DateTime<chrono::Utc>>>,
+}
+
+impl UpdateNoteSchema {
+    pub fn trim(&mut self) {
+        self.title = self.title.take().map(|title| title.trim().to_string());
+        if let Some(Some(content)) = &mut self.content {
+            *content = content.trim().to_string();
+        }
+    }
+
+    // Only the fields present in the patch are checked.
+    pub fn validate(&self, max_content_bytes: usize) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if let Some(title) = &self.title {
+            validate_title(title, &mut errors);
+        }
+        if let Some(Some(content)) = &self.content {
+            validate_content(content, max_content_bytes, &mut errors);
+        }
+
+        errors
+    }
+}
+
+// Distingue un campo omitido (None) de uno enviado como null (Some(None)).
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_tells_omitted_content_from_null() {
+        let parse = |json| serde_json::from_str::<UpdateNoteSchema>(json).unwrap();
+        assert_eq!(parse(r#"{}"#).content, None);
+        assert_eq!(parse(r#"{"content": null}"#).content, Some(None));
+        assert_eq!(
+            parse(r#"{"content": "texto"}"#).content,
+            Some(Some("texto".to_string()))
+        );
+    }
+
+    #[test]
+    fn update_tells_omitted_remind_at_from_null() {
+        let parse = |json| serde_json::from_str::<UpdateNoteSchema>(json).unwrap();
+        assert_eq!(parse(r#"{"title": "a"}"#).remind_at, None);
+        assert_eq!(parse(r#"{"remind_at": null}"#).remind_at, Some(None));
+        assert!(matches!(
+            parse(r#"{"remind_at": "2026-01-01T00:00:00Z"}"#).remind_at,
+            Some(Some(_))
+        ));
+    }
+
+    #[test]
+    fn plain_text_is_not_binary() {
+        assert!(!looks_binary(""));
+        assert!(!looks_binary("hola\r\nmundo\tcon tildes: año"));
+    }
+
+    #[test]
+    fn nul_byte_is_binary() {
+        assert!(looks_binary("abc\0def"));
+    }
+
+    #[test]
+    fn binary_depends_on_the_ratio_of_control_characters() {
+        let mostly_text = format!("\u{1}{}", "a".repeat(19));
+        assert!(!looks_binary(&mostly_text));
+        assert!(looks_binary("\u{1}\u{2}abc"));
+        assert!(looks_binary("\u{FFFD}\u{FFFD}abc"));
+    }
 }
@@ -0,0 +1,124 @@
+use std::{sync::Arc, time::Duration};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::sync::mpsc;
+
+use crate::{model::NoteEvent, AppState};
+
+const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+
+// Forwards every NoteEvent to the configured URLs. Each delivery runs in its
+// own task, so a slow receiver never holds up the others or the handlers
+// that published the event. Without URLs the queue is dropped and publishing
+// to it does nothing.
+pub async fn run(data: Arc<AppState>, mut events: mpsc::UnboundedReceiver<NoteEvent>) {
+    if data.config.webhook_urls.is_empty() {
+        return;
+    }
+
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            tracing::error!("no se pudo crear el cliente de webhooks: {:?}", err);
+            return;
+        }
+    };
+
+    while let Some(event) = events.recv().await {
+        let payload = match serde_json::to_vec(&serde_json::json!({
+            "event": event,
+            "sent_at": chrono::Utc::now(),
+        })) {
+            Ok(payload) => payload,
+            Err(err) => {
+                tracing::error!("webhooks: evento no serializable: {:?}", err);
+                continue;
+            }
+        };
+        let signature = data
+            .config
+            .webhook_secret
+            .as_deref()
+            .map(|secret| sign(secret, &payload));
+
+        for url in &data.config.webhook_urls {
+            tokio::spawn(deliver(
+                client.clone(),
+                url.clone(),
+                payload.clone(),
+                signature.clone(),
+                data.config.webhook_max_attempts,
+            ));
+        }
+    }
+}
+
+// Retries on network errors and non-2xx answers, backing off 1s, 2s, 4s...
+async fn deliver(
+    client: reqwest::Client,
+    url: String,
+    payload: Vec<u8>,
+    signature: Option<String>,
+    max_attempts: u32,
+) {
+    for attempt in 1..=max_attempts.max(1) {
+        let mut request = client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => tracing::warn!(
+                "webhook {} respondio {} (intento {}/{})",
+                url,
+                response.status(),
+                attempt,
+                max_attempts
+            ),
+            Err(err) => tracing::warn!(
+                "webhook {} fallo: {} (intento {}/{})",
+                url, err, attempt, max_attempts
+            ),
+        }
+
+        if attempt < max_attempts {
+            tokio::time::sleep(Duration::from_secs(1 << (attempt - 1).min(6))).await;
+        }
+    }
+}
+
+// "sha256=<hex HMAC-SHA256 of the raw body>", the same shape GitHub uses.
+fn sign(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(payload);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_matches_the_rfc_4231_vector() {
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn sign_depends_on_the_secret() {
+        assert_ne!(sign("uno", b"{}"), sign("dos", b"{}"));
+    }
+}